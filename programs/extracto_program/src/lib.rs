@@ -1,10 +1,8 @@
 //love sonechka-zvezdochka
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{
-    instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program,
-};
-use anchor_lang::InstructionData;
-use clockwork_sdk::state::{Thread, ThreadAccount};
+use anchor_lang::solana_program::{hash::hash, instruction::Instruction, system_program};
+use anchor_lang::{Discriminator, InstructionData};
+use clockwork_sdk::state::{Thread, ThreadAccount, ThreadResponse, Trigger};
 use gpl_session::{session_auth_or, Session, SessionError, SessionToken};
 
 declare_id!("CHPyHid6CQzErEYrsuinBRsjPdsUZdUzgKMCc6VZ9Tjf");
@@ -13,17 +11,179 @@ declare_id!("CHPyHid6CQzErEYrsuinBRsjPdsUZdUzgKMCc6VZ9Tjf");
 pub enum GameErrorCode {
     #[msg("Wrong Authority")]
     WrongAuthority,
+    #[msg("Invalid Season Duration")]
+    InvalidSeasonDuration,
+    #[msg("Season Not Over")]
+    SeasonNotOver,
+    #[msg("Name Too Short")]
+    NameTooShort,
+    #[msg("Name Too Long")]
+    NameTooLong,
+    #[msg("Invalid Name Character")]
+    InvalidNameCharacter,
+    #[msg("Already In Run")]
+    AlreadyInRun,
+    #[msg("Not In Run")]
+    NotInRun,
+    #[msg("Wrong Thread")]
+    WrongThread,
+    #[msg("No Ticks Due")]
+    NoTicksDue,
+    #[msg("Invalid Card Slot")]
+    InvalidCardSlot,
+    #[msg("Invalid Character Slot")]
+    InvalidCharacterSlot,
+    #[msg("Empty Slot")]
+    EmptySlot,
+    #[msg("Invalid Card Type")]
+    InvalidCardType,
+    #[msg("Stat Overflow")]
+    StatOverflow,
+    #[msg("Counter Overflow")]
+    CounterOverflow,
+    #[msg("Missing Bump")]
+    MissingBump,
+    #[msg("Invalid Expected Waves")]
+    InvalidExpectedWaves,
+    #[msg("Run Paused")]
+    RunPaused,
+    #[msg("Run Not Paused")]
+    RunNotPaused,
+    #[msg("Pause Budget Exhausted")]
+    PauseBudgetExhausted,
+    #[msg("Run Not Expired")]
+    RunNotExpired,
+    #[msg("Debug Instructions Disabled")]
+    DebugInstructionsDisabled,
+    #[msg("Program Paused")]
+    ProgramPaused,
+    #[msg("Not Pending Admin")]
+    NotPendingAdmin,
+    #[msg("Account Not Migrated")]
+    AccountNotMigrated,
+    #[msg("Unknown Account Layout")]
+    UnknownAccountLayout,
+    #[msg("Invalid Run Slot")]
+    InvalidRunSlot,
+    #[msg("Challenge Not Today")]
+    ChallengeNotToday,
+    #[msg("Daily Challenge Already Played")]
+    DailyChallengeAlreadyPlayed,
+    #[msg("Daily Leaderboard Required")]
+    DailyLeaderboardRequired,
+    #[msg("Daily Challenge Is Played On Normal")]
+    DailyChallengeDifficulty,
+    #[msg("Invalid Mutators")]
+    InvalidMutators,
+    #[msg("Daily Challenge Takes No Mutators")]
+    DailyChallengeMutators,
+    #[msg("Name Taken")]
+    NameTaken,
+    #[msg("Ticks Overdue")]
+    TicksOverdue,
+    #[msg("Insufficient Experience")]
+    InsufficientExperience,
+    #[msg("Heroes Defeated")]
+    HeroesDefeated,
 }
 
 pub const PLAYER_SEED: &[u8] = b"player";
 pub const RUN_SEED: &[u8] = b"run";
 pub const THREAD_AUTHORITY_SEED: &[u8] = b"thread_authority";
+pub const LEADERBOARD_SEED: &[u8] = b"leaderboard";
+pub const SEASON_SEED: &[u8] = b"season";
+pub const SEASON_ARCHIVE_SEED: &[u8] = b"season_archive";
+pub const RUN_RECORD_SEED: &[u8] = b"run_record";
+pub const PLAYER_STATS_SEED: &[u8] = b"player_stats";
+pub const NAME_SEED: &[u8] = b"name";
+pub const CRANK_VAULT_SEED: &[u8] = b"crank_vault";
+pub const PROGRAM_ADMIN_SEED: &[u8] = b"program_admin";
+pub const DAILY_CHALLENGE_SEED: &[u8] = b"daily_challenge";
 
-pub const COOLDOWN_BY_TYPE: [u8; 7] = [5, 4, 6, 4, 6, 8, 10];
-pub const HEALTH_BY_TYPE: [u8; 7] = [5, 7, 10, 5, 5, 10, 20];
-pub const ATTACK_BY_TYPE: [u8; 7] = [2, 1, 3, 1, 1, 2, 5];
+pub const MIN_NAME_LENGTH: usize = 3;
+pub const MAX_NAME_LENGTH: usize = 16;
+
+pub const LEADERBOARD_SIZE: usize = 10;
+pub const LEADERBOARD_NAME_LENGTH: usize = 16;
+pub const TICKS_PER_WAVE: u32 = 30;
+// catch-up is bounded so a call stays well inside the default compute budget
+pub const MAX_TICKS_PER_CALL: u32 = 10;
+pub const CRANK_BOUNTY_LAMPORTS: u64 = 5000;
+// the thread fires less often in the quiet first waves; catch-up keeps game time the same
+pub const EARLY_WAVE_THREAD_INTERVAL_SECONDS: u8 = 2;
+pub const THREAD_INTERVAL_SECONDS: u8 = 1;
+pub const EARLY_WAVES: u16 = 2;
+// a run nobody has acted on for this long can be expired by anyone
+pub const RUN_INACTIVITY_TIMEOUT_SECONDS: i64 = 60 * 60;
+// paid to whoever expires a run, on top of the rent they put up for its record
+pub const CLEANUP_REWARD_LAMPORTS: u64 = 100_000;
+// game time a run may spend frozen in total; pausing past it lets the clock run again
+pub const MAX_PAUSE_SECONDS_PER_RUN: i64 = 300;
+// thread funding: one clockwork fee per second of the expected run at its difficulty's tick rate,
+// plus headroom for the auto-finish and the thread's own rent
+pub const THREAD_FEE_LAMPORTS: u64 = 1000;
+pub const DEFAULT_EXPECTED_RUN_WAVES: u16 = 10;
+pub const MAX_EXPECTED_RUN_WAVES: u16 = 200;
+pub const THREAD_FUNDING_BUFFER_LAMPORTS: u64 = 10_000_000;
+// warn once fewer than this many executions are left in the thread
+pub const THREAD_LOW_BALANCE_EXECUTIONS: u64 = 60;
+
+pub const COOLDOWN_BY_TYPE: [u16; 7] = [5, 4, 6, 4, 6, 8, 10];
+pub const HEALTH_BY_TYPE: [u16; 7] = [5, 7, 10, 5, 5, 10, 20];
+pub const ATTACK_BY_TYPE: [u16; 7] = [2, 1, 3, 1, 1, 2, 5];
+// enemy stat scaling by the wave they spawn in, as (wave, percent) points: linear between points,
+// flat after the last one
+pub const ENEMY_HEALTH_CURVE: [(u16, u16); 5] =
+    [(1, 100), (5, 150), (10, 250), (20, 500), (40, 1000)];
+pub const ENEMY_ATTACK_CURVE: [(u16, u16); 5] =
+    [(1, 100), (5, 125), (10, 175), (20, 300), (40, 500)];
 pub const CARD_COST_BY_TYPE: [u8; 3] = [1, 2, 3];
 
+// scoring: only outcomes the program itself observed count towards a run's score
+pub const KILL_SCORE_BY_TYPE: [u64; 7] = [0, 0, 0, 10, 15, 25, 60];
+pub const WAVE_CLEARED_SCORE: u64 = 100;
+pub const SURVIVAL_SCORE_PER_TICK: u64 = 1;
+// extracting pays in proportion to the waves cleared, in full from this many on
+pub const EXTRACTION_BONUS_SCORE: u64 = 500;
+pub const EXTRACTION_FULL_BONUS_WAVES: u64 = 5;
+
+pub const CHARACTER_TYPE_COUNT: usize = 7;
+pub const HERO_TYPE_COUNT: usize = 3;
+pub const CARD_TYPE_COUNT: usize = 3;
+// runs a player can keep side by side, e.g. casual, ranked and daily challenge; at most 8
+pub const MAX_RUN_SLOTS: u8 = 3;
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+pub const DEFAULT_LINEUP: [u8; HERO_TYPE_COUNT] = [0, 1, 2];
+// keeps the spawn stream apart from the card stream drawn from the same seed
+pub const SPAWN_RNG_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+pub const MAX_DAILY_ENEMY_HEALTH_BONUS: u8 = 3;
+pub const MAX_DAILY_ENEMY_ATTACK_BONUS: u8 = 1;
+pub const MAX_DAILY_STARTING_EXPERIENCE: u16 = 4;
+
+// difficulty tables, indexed by `Difficulty`
+pub const DIFFICULTY_COUNT: usize = 3;
+// percent of their base stats enemies spawn with
+pub const ENEMY_HEALTH_PERCENT_BY_DIFFICULTY: [u16; DIFFICULTY_COUNT] = [75, 100, 150];
+pub const ENEMY_ATTACK_PERCENT_BY_DIFFICULTY: [u16; DIFFICULTY_COUNT] = [75, 100, 150];
+// relative odds of spawning enemy types 3, 4, 5 and 6
+pub const SPAWN_WEIGHTS_BY_DIFFICULTY: [[u8; 4]; DIFFICULTY_COUNT] =
+    [[4, 3, 2, 1], [1, 1, 1, 1], [1, 2, 3, 4]];
+// game time between ticks; catch-up lets a tick be shorter than the thread's interval
+pub const TICK_INTERVAL_MS_BY_DIFFICULTY: [u32; DIFFICULTY_COUNT] = [1500, 1000, 750];
+pub const SCORE_PERCENT_BY_DIFFICULTY: [u64; DIFFICULTY_COUNT] = [50, 100, 200];
+
+// run mutators, bits of `RunData.mutators` picked at start
+pub const MUTATOR_TOUGH_ENEMIES: u8 = 1 << 0;
+pub const MUTATOR_EXPENSIVE_CARDS: u8 = 1 << 1;
+pub const MUTATOR_NO_HEALING: u8 = 1 << 2;
+pub const MUTATOR_COUNT: usize = 3;
+pub const ALL_MUTATORS: u8 = (1 << MUTATOR_COUNT) - 1;
+// extra score percent each mutator adds, by bit
+pub const MUTATOR_SCORE_BONUS_PERCENT: [u64; MUTATOR_COUNT] = [50, 30, 20];
+pub const TOUGH_ENEMIES_HEALTH_PERCENT: u16 = 150;
+pub const EXPENSIVE_CARDS_COST_MULTIPLIER: u8 = 2;
+
 // 0 - increase max health
 // 1 - increase attack damage
 // 2 - attack faster
@@ -32,149 +192,564 @@ pub const CARD_COST_BY_TYPE: [u8; 3] = [1, 2, 3];
 pub mod extracto_program {
     use super::*;
 
+    /// Each difficulty ranks on its own board.
+    pub fn init_leaderboard(ctx: Context<InitLeaderboard>, _difficulty: Difficulty) -> Result<()> {
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        leaderboard.entries = Vec::new();
+        leaderboard.version = Leaderboard::VERSION;
+        Ok(())
+    }
+
+    /// One-time setup, only the program's upgrade authority can claim the admin role.
+    pub fn init_program_admin(ctx: Context<InitProgramAdmin>) -> Result<()> {
+        let program_admin = &mut ctx.accounts.program_admin;
+        program_admin.admin = ctx.accounts.authority.key();
+        program_admin.pending_admin = Pubkey::default();
+        program_admin.paused = false;
+        program_admin.version = ProgramAdmin::VERSION;
+        Ok(())
+    }
+
+    /// First half of the handover; the new admin has to accept before anything changes.
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.program_admin.pending_admin = new_admin;
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let program_admin = &mut ctx.accounts.program_admin;
+        program_admin.admin = program_admin.pending_admin;
+        program_admin.pending_admin = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let program_admin = &mut ctx.accounts.program_admin;
+        let now = Clock::get()?.unix_timestamp;
+        if paused && !program_admin.paused {
+            program_admin.paused_at = now;
+        } else if !paused && program_admin.paused {
+            program_admin.unpaused_at = now;
+        }
+        program_admin.paused = paused;
+        Ok(())
+    }
+
+    /// Emergency stop for a single run: deletes its thread, refunds everything to the player and
+    /// records the run without ranking it.
+    pub fn force_finish_run(ctx: Context<ForceFinishRun>) -> Result<()> {
+        let run = &mut ctx.accounts.run;
+        let player_data = &mut ctx.accounts.player_data;
+        let player = &ctx.accounts.player;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread_authority = &ctx.accounts.thread_authority;
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        let season = &ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let run_record = &mut ctx.accounts.run_record;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let system_program = &ctx.accounts.system_program;
+        let now = Clock::get()?.unix_timestamp;
+
+        if run.thread != Pubkey::default() {
+            let thread = ctx
+                .accounts
+                .thread
+                .as_ref()
+                .ok_or(GameErrorCode::WrongThread)?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadDelete {
+                    authority: thread_authority.to_account_info(),
+                    close_to: player.to_account_info(),
+                    thread: thread.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, run.authority.as_ref(), &[bump]]],
+            ))?;
+        }
+
+        let vault_bump = *ctx
+            .bumps
+            .get("crank_vault")
+            .ok_or(GameErrorCode::MissingBump)?;
+        let run_key = run.key();
+        withdraw_from_crank_vault(
+            crank_vault,
+            player,
+            system_program,
+            &[CRANK_VAULT_SEED, run_key.as_ref(), &[vault_bump]],
+            crank_vault.lamports(),
+        )?;
+
+        // Unranked whatever happens, so there is no backlog worth simulating; a stuck run with a
+        // long one must still be stoppable.
+        finalize_run(
+            run,
+            player_data,
+            player_stats,
+            run_record,
+            leaderboard,
+            season,
+            season_leaderboard,
+            ctx.accounts.daily_leaderboard.as_deref_mut().map(|board| &mut **board),
+            RunEndReason::ForceFinished,
+            now,
+        )?;
+
+        Ok(())
+    }
+
+    /// Brings a run account up to `RunData::VERSION`, growing it and paying the extra rent.
+    /// Runs saved before versioning were on a thread this program no longer drives, so they come
+    /// back idle.
+    pub fn migrate_run(ctx: Context<MigrateRun>, run_slot: u8) -> Result<()> {
+        let run = &ctx.accounts.run;
+        let player = &ctx.accounts.player;
+        let system_program = &ctx.accounts.system_program;
+
+        // accounts only ever grow, so the size tells which stat widths the slots were written with
+        let stored_len = run.data_len();
+        // the new tail is zeroed, so the fields since the legacy layout start out as defaults
+        grow_account(run, player, system_program, RunData::SPACE)?;
+        upgrade_run_data(&mut run.try_borrow_mut_data()?, stored_len, run_slot)?;
+
+        Ok(())
+    }
+
+    /// Brings a player account up to `PlayerData::VERSION`; see `migrate_run`.
+    pub fn migrate_player(ctx: Context<MigratePlayer>) -> Result<()> {
+        let player_data = &ctx.accounts.player_data;
+        let player = &ctx.accounts.player;
+        let system_program = &ctx.accounts.system_program;
+
+        // the name is the only variable-length field, so its length prefix fixes the new size
+        let name_length = {
+            let data = player_data.try_borrow_data()?;
+            let prefix = data
+                .get(8 + 32..8 + 32 + 4)
+                .ok_or(GameErrorCode::UnknownAccountLayout)?;
+            u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize
+        };
+        grow_account(player_data, player, system_program, PlayerData::space(name_length))?;
+
+        let mut data = PlayerData::try_deserialize(&mut &player_data.try_borrow_data()?[..])?;
+        if data.version < PlayerData::VERSION {
+            if data.version == 0 {
+                // matches the run, which `migrate_run` leaves idle
+                data.is_in_run = false;
+            }
+            if data.version < 2 {
+                // before run slots the only run was slot 0
+                data.active_runs = 0;
+                data.set_run_active(0, data.is_in_run);
+            }
+            if data.version < 3 {
+                data.last_daily_day = 0;
+            }
+            data.version = PlayerData::VERSION;
+            data.try_serialize(&mut &mut player_data.try_borrow_mut_data()?[..])?;
+        }
+
+        if let Some(player_stats) = &ctx.accounts.player_stats {
+            grow_account(player_stats, player, system_program, PlayerStats::SPACE)?;
+            let mut stats =
+                PlayerStats::try_deserialize(&mut &player_stats.try_borrow_data()?[..])?;
+            if stats.version < PlayerStats::VERSION {
+                if stats.version < 2 {
+                    // every record so far was counted by the player account
+                    stats.runs_recorded = data.runs_finished;
+                }
+                stats.version = PlayerStats::VERSION;
+                stats.try_serialize(&mut &mut player_stats.try_borrow_mut_data()?[..])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Brings the admin account up to `ProgramAdmin::VERSION`; see `migrate_run`.
+    pub fn migrate_program_admin(ctx: Context<MigrateProgramAdmin>) -> Result<()> {
+        let program_admin = &ctx.accounts.program_admin;
+        let admin = &ctx.accounts.admin;
+        let system_program = &ctx.accounts.system_program;
+
+        // the admin key leads every layout, so it can be checked before growing
+        {
+            let data = program_admin.try_borrow_data()?;
+            let stored_admin = data
+                .get(8..8 + 32)
+                .ok_or(GameErrorCode::UnknownAccountLayout)?;
+            require!(
+                stored_admin == admin.key().as_ref(),
+                GameErrorCode::WrongAuthority
+            );
+        }
+        grow_account(program_admin, admin, system_program, ProgramAdmin::SPACE)?;
+
+        let mut data = ProgramAdmin::try_deserialize(&mut &program_admin.try_borrow_data()?[..])?;
+        if data.version < ProgramAdmin::VERSION {
+            if data.version < 2 {
+                data.paused_at = 0;
+                data.unpaused_at = 0;
+            }
+            data.version = ProgramAdmin::VERSION;
+            data.try_serialize(&mut &mut program_admin.try_borrow_mut_data()?[..])?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the challenge for today, UTC. Anyone can, and the first caller does it for everyone.
+    pub fn init_daily_challenge(ctx: Context<InitDailyChallenge>, day: u32) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            day == utc_day(clock.unix_timestamp),
+            GameErrorCode::ChallengeNotToday
+        );
+
+        let daily_challenge = &mut ctx.accounts.daily_challenge;
+        let daily_leaderboard = &mut ctx.accounts.daily_leaderboard;
+
+        let entropy = hash(
+            &[
+                DAILY_CHALLENGE_SEED,
+                &day.to_le_bytes(),
+                &clock.slot.to_le_bytes(),
+            ]
+            .concat(),
+        );
+        let mut seed_bytes = [0u8; 8];
+        seed_bytes.copy_from_slice(&entropy.to_bytes()[..8]);
+        let seed = u64::from_le_bytes(seed_bytes).max(1);
+
+        // Fisher-Yates over the heroes, then the modifiers, all from the day's seed.
+        let mut rng_state = seed;
+        let mut lineup = DEFAULT_LINEUP;
+        for i in (1..lineup.len()).rev() {
+            rng_state = xorshift64(rng_state);
+            lineup.swap(i, (rng_state % (i as u64 + 1)) as usize);
+        }
+        rng_state = xorshift64(rng_state);
+        let enemy_health_bonus = (rng_state % (MAX_DAILY_ENEMY_HEALTH_BONUS as u64 + 1)) as u8;
+        rng_state = xorshift64(rng_state);
+        let enemy_attack_bonus = (rng_state % (MAX_DAILY_ENEMY_ATTACK_BONUS as u64 + 1)) as u8;
+        rng_state = xorshift64(rng_state);
+        let starting_experience = (rng_state % (MAX_DAILY_STARTING_EXPERIENCE as u64 + 1)) as u16;
+
+        daily_challenge.day = day;
+        daily_challenge.seed = seed;
+        daily_challenge.lineup = lineup;
+        daily_challenge.modifiers = ChallengeModifiers {
+            enemy_health_bonus,
+            enemy_attack_bonus,
+            starting_experience,
+        };
+        daily_challenge.created_at = clock.unix_timestamp;
+        daily_challenge.version = DailyChallenge::VERSION;
+
+        daily_leaderboard.entries = Vec::new();
+        daily_leaderboard.version = Leaderboard::VERSION;
+
+        Ok(())
+    }
+
+    pub fn init_season(ctx: Context<InitSeason>, duration: i64) -> Result<()> {
+        require!(duration > 0, GameErrorCode::InvalidSeasonDuration);
+
+        let season = &mut ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let now = Clock::get()?.unix_timestamp;
+
+        season.id = 1;
+        season.version = Season::VERSION;
+        season.duration = duration;
+        season.start_timestamp = now;
+        season.end_timestamp = now
+            .checked_add(duration)
+            .ok_or(GameErrorCode::InvalidSeasonDuration)?;
+        season_leaderboard.entries = Vec::new();
+        season_leaderboard.version = Leaderboard::VERSION;
+
+        Ok(())
+    }
+
+    pub fn rollover_season(ctx: Context<RolloverSeason>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let archive = &mut ctx.accounts.archive;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(now >= season.end_timestamp, GameErrorCode::SeasonNotOver);
+
+        archive.season_id = season.id;
+        archive.start_timestamp = season.start_timestamp;
+        archive.end_timestamp = season.end_timestamp;
+        archive.entries = std::mem::take(&mut season_leaderboard.entries);
+        archive.version = SeasonArchive::VERSION;
+
+        // the next season starts now rather than at the old end, so a late rollover doesn't
+        // open a season that is already over
+        season.id = season
+            .id
+            .checked_add(1)
+            .ok_or(GameErrorCode::CounterOverflow)?;
+        season.start_timestamp = now;
+        season.end_timestamp = now
+            .checked_add(season.duration)
+            .ok_or(GameErrorCode::InvalidSeasonDuration)?;
+
+        Ok(())
+    }
+
     pub fn init_player(ctx: Context<InitPlayer>, name: String) -> Result<()> {
         let player = &ctx.accounts.player;
         let player_data = &mut ctx.accounts.player_data;
         let run = &mut ctx.accounts.run;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let name_registry = &mut ctx.accounts.name_registry;
+
+        validate_name(&name)?;
+
+        // a registry that was already there belongs to someone else
+        require!(
+            name_registry.player == Pubkey::default(),
+            GameErrorCode::NameTaken
+        );
+
+        name_registry.player = player.key();
+        name_registry.name = name.clone();
+        name_registry.version = NameRegistry::VERSION;
 
         player_data.authority = player.key();
         player_data.name = name;
-        player_data.runs_finished = 0;
+        // Stats and run records outlive a closed player, so a re-created one carries on numbering
+        // its records where the last one left off.
+        player_data.runs_finished = player_stats.runs_recorded;
         player_data.is_in_run = false;
+        player_data.active_runs = 0;
+        player_data.last_daily_day = 0;
         player_data.best_score = 0;
+        player_data.version = PlayerData::VERSION;
 
         run.authority = player.key();
         run.score = 0;
+        run.version = RunData::VERSION;
+
+        player_stats.authority = player.key();
+        player_stats.version = PlayerStats::VERSION;
+
+        Ok(())
+    }
+
+    /// Creates one of the player's extra run slots; slot 0 comes with `init_player`.
+    pub fn open_run_slot(ctx: Context<OpenRunSlot>, run_slot: u8) -> Result<()> {
+        require!(
+            run_slot > 0 && run_slot < MAX_RUN_SLOTS,
+            GameErrorCode::InvalidRunSlot
+        );
+
+        let run = &mut ctx.accounts.run;
+        run.authority = ctx.accounts.player.key();
+        run.run_slot = run_slot;
+        run.version = RunData::VERSION;
+
+        Ok(())
+    }
+
+    pub fn close_run_slot(_ctx: Context<CloseRunSlot>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn rename_player(ctx: Context<RenamePlayer>, new_name: String) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let player_data = &mut ctx.accounts.player_data;
+        let old_name_registry = &ctx.accounts.old_name_registry;
+        let new_name_registry = &mut ctx.accounts.new_name_registry;
+
+        validate_name(&new_name)?;
+
+        // a registry that was already there belongs to someone else, unless only the case changed
+        require!(
+            new_name_registry.player == Pubkey::default()
+                || new_name_registry.player == player.key(),
+            GameErrorCode::NameTaken
+        );
+
+        // A case-only rename keeps the same registry entry, which is updated in place. Players
+        // created before the registry existed have nothing to free.
+        if name_seed(&new_name) != name_seed(&player_data.name) {
+            release_name(old_name_registry, player)?;
+        }
+
+        new_name_registry.player = player.key();
+        new_name_registry.name = new_name.clone();
+        new_name_registry.version = NameRegistry::VERSION;
+        player_data.name = new_name;
+
+        Ok(())
+    }
 
+    pub fn close_player(ctx: Context<ClosePlayer>) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let name_registry = &ctx.accounts.name_registry;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread_authority = &ctx.accounts.thread_authority;
+
+        if let Some(thread) = &ctx.accounts.thread {
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadDelete {
+                    authority: thread_authority.to_account_info(),
+                    close_to: player.to_account_info(),
+                    thread: thread.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, player.key().as_ref(), &[bump]]],
+            ))?;
+        }
+
+        release_name(name_registry, player)?;
+
+        // player_data and run are closed by their `close` constraints
         Ok(())
     }
 
-    pub fn start_new_run(ctx: Context<StartNewRun>, thread_id: Vec<u8>) -> Result<()> {
+    pub fn start_new_run(
+        ctx: Context<StartNewRun>,
+        thread_id: Vec<u8>,
+        expected_waves: Option<u16>,
+        difficulty: Difficulty,
+        mutators: u8,
+    ) -> Result<()> {
         let player = &ctx.accounts.player;
         let run = &mut ctx.accounts.run;
         let player_data = &mut ctx.accounts.player_data;
         let system_program = &ctx.accounts.system_program;
         let clockwork_program = &ctx.accounts.clockwork_program;
-        let thread = &ctx.accounts.thread;
         let thread_authority = &ctx.accounts.thread_authority;
 
-        // 1️⃣ Prepare an instruction to be automated.
-        let target_ix = Instruction {
-            program_id: ID,
-            accounts: crate::accounts::IncrementViaThread {
-                run: run.key(),
-                thread: thread.key(),
-                thread_authority: thread_authority.key(),
-            }
-            .to_account_metas(Some(true)),
-            data: crate::instruction::IncrementViaThread {}.data(),
-        };
+        require!(
+            mutators & !ALL_MUTATORS == 0,
+            GameErrorCode::InvalidMutators
+        );
 
-        // 2️⃣ Define a trigger for the thread (every 10 secs).
-        let trigger = clockwork_sdk::state::Trigger::Cron {
-            schedule: "*/1 * * * * * *".into(),
-            skippable: true,
-        };
+        // Without a thread the run is advanced by `tick` cranks instead.
+        let mut run_thread = Pubkey::default();
+        if let Some(thread) = &ctx.accounts.thread {
+            // 1️⃣ Prepare an instruction to be automated.
+            let target_ix = Instruction {
+                program_id: ID,
+                accounts: crate::accounts::IncrementViaThread {
+                    run: run.key(),
+                    player_data: player_data.key(),
+                    thread: thread.key(),
+                    thread_authority: thread_authority.key(),
+                    program_admin: ctx.accounts.program_admin.key(),
+                }
+                .to_account_metas(Some(true)),
+                data: crate::instruction::IncrementViaThread {}.data(),
+            };
 
-        // 3️⃣ Create thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_create(
-            CpiContext::new_with_signer(
-                clockwork_program.to_account_info(),
-                clockwork_sdk::cpi::ThreadCreate {
-                    payer: player.to_account_info(),
-                    system_program: system_program.to_account_info(),
-                    thread: thread.to_account_info(),
-                    authority: thread_authority.to_account_info(),
-                },
-                &[&[THREAD_AUTHORITY_SEED, player.key().as_ref(), &[bump]]], //this is signer seeds needed by the called program to verify PDA signature
-            ),
-            100000000,              // amount
-            thread_id,              // id
-            vec![target_ix.into()], // instructions
-            trigger,                // trigger
-        )?;
+            // 2️⃣ Define a trigger for the thread, retuned by its responses as the run goes on.
+            let trigger = thread_trigger(EARLY_WAVE_THREAD_INTERVAL_SECONDS);
+
+            // 3️⃣ Create thread via CPI, funded for the expected run length.
+            let funding = thread_funding_lamports(
+                expected_waves.unwrap_or(DEFAULT_EXPECTED_RUN_WAVES),
+                difficulty,
+            )?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_create(
+                CpiContext::new_with_signer(
+                    clockwork_program.to_account_info(),
+                    clockwork_sdk::cpi::ThreadCreate {
+                        payer: player.to_account_info(),
+                        system_program: system_program.to_account_info(),
+                        thread: thread.to_account_info(),
+                        authority: thread_authority.to_account_info(),
+                    },
+                    &[&[THREAD_AUTHORITY_SEED, player.key().as_ref(), &[bump]]], //this is signer seeds needed by the called program to verify PDA signature
+                ),
+                funding,                // amount
+                thread_id,              // id
+                vec![target_ix.into()], // instructions
+                trigger,                // trigger
+            )?;
+
+            run_thread = thread.key();
+        }
+
+        let clock = Clock::get()?;
+        let mut player_entropy = [0u8; 8];
+        player_entropy.copy_from_slice(&player.key().to_bytes()[..8]);
 
-        player_data.is_in_run = true;
+        // A daily run plays the day's seed and lineup, once per player.
+        let mut lineup = DEFAULT_LINEUP;
+        run.daily_day = 0;
+        run.modifiers = ChallengeModifiers::default();
+        run.seed = xorshift64(clock.slot ^ u64::from_le_bytes(player_entropy)).max(1);
+        if let Some(daily_challenge) = &ctx.accounts.daily_challenge {
+            // everyone plays the same day on the same terms
+            require!(
+                difficulty == Difficulty::Normal,
+                GameErrorCode::DailyChallengeDifficulty
+            );
+            require!(mutators == 0, GameErrorCode::DailyChallengeMutators);
+            require!(
+                player_data.last_daily_day != daily_challenge.day,
+                GameErrorCode::DailyChallengeAlreadyPlayed
+            );
+            player_data.last_daily_day = daily_challenge.day;
+            run.daily_day = daily_challenge.day;
+            run.modifiers = daily_challenge.modifiers;
+            run.seed = daily_challenge.seed;
+            lineup = daily_challenge.lineup;
+        }
+
+        player_data.set_run_active(run.run_slot, true);
+        run.status = RunStatus::Active;
+        run.thread = run_thread;
+        run.difficulty = difficulty;
+        run.mutators = mutators;
         run.score = 0;
+        run.score_breakdown = ScoreBreakdown::default();
+        run.rng_state = run.seed;
+        run.spawn_rng_state = xorshift64(run.seed ^ SPAWN_RNG_SALT).max(1);
+        run.experience = run.modifiers.starting_experience;
+        run.started_at = clock.unix_timestamp;
+        run.last_tick_at = clock.unix_timestamp;
+        run.tick_carry_ms = 0;
+        run.skipped_pause_at = ctx.accounts.program_admin.paused_at;
+        run.thread_interval = EARLY_WAVE_THREAD_INTERVAL_SECONDS;
+        run.paused_at = 0;
+        run.pause_used = 0;
+        run.refresh_deadline(clock.unix_timestamp);
+        run.ticks = 0;
+        run.wave = 1;
+        run.kills = 0;
+        run.kills_by_type = [0; CHARACTER_TYPE_COUNT];
+        run.damage_dealt = 0;
+        run.damage_taken = 0;
+        run.cards_played_by_type = [0; CARD_TYPE_COUNT];
+        run.heroes_lost_by_type = [0; HERO_TYPE_COUNT];
 
-        run.slots[0] = Some(CharacterInfo {
-            id: 0,
-            alignment: 0,
-            character_type: 0,
-            cooldown: COOLDOWN_BY_TYPE[0],
-            cooldown_timer: COOLDOWN_BY_TYPE[0],
-            max_health: HEALTH_BY_TYPE[0],
-            health: HEALTH_BY_TYPE[0],
-            attack_damage: ATTACK_BY_TYPE[0],
-            state: 0
-        });
-        run.slots[1] = Some(CharacterInfo {
-            id: 1,
-            alignment: 0,
-            character_type: 1,
-            cooldown: COOLDOWN_BY_TYPE[1],
-            cooldown_timer: COOLDOWN_BY_TYPE[1],
-            max_health: HEALTH_BY_TYPE[1],
-            health: HEALTH_BY_TYPE[1],
-            attack_damage: ATTACK_BY_TYPE[1],
-            state: 0
-        });
-        run.slots[2] = Some(CharacterInfo {
-            id: 2,
-            alignment: 0,
-            character_type: 2,
-            cooldown: COOLDOWN_BY_TYPE[2],
-            cooldown_timer: COOLDOWN_BY_TYPE[2],
-            max_health: HEALTH_BY_TYPE[2],
-            health: HEALTH_BY_TYPE[2],
-            attack_damage: ATTACK_BY_TYPE[2],
-            state: 0
-        });
-        run.slots[3] = Some(CharacterInfo {
-            id: 3,
-            alignment: 1,
-            character_type: 3,
-            cooldown: COOLDOWN_BY_TYPE[3],
-            cooldown_timer: COOLDOWN_BY_TYPE[3],
-            max_health: HEALTH_BY_TYPE[3],
-            health: HEALTH_BY_TYPE[3],
-            attack_damage: ATTACK_BY_TYPE[3],
-            state: 0
-        });
-        run.slots[4] = Some(CharacterInfo {
-            id: 4,
-            alignment: 1,
-            character_type: 4,
-            cooldown: COOLDOWN_BY_TYPE[4],
-            cooldown_timer: COOLDOWN_BY_TYPE[4],
-            max_health: HEALTH_BY_TYPE[4],
-            health: HEALTH_BY_TYPE[4],
-            attack_damage: ATTACK_BY_TYPE[4],
-            state: 0
-        });
-        run.slots[5] = Some(CharacterInfo {
-            id: 5,
-            alignment: 1,
-            character_type: 5,
-            cooldown: COOLDOWN_BY_TYPE[5],
-            cooldown_timer: COOLDOWN_BY_TYPE[5],
-            max_health: HEALTH_BY_TYPE[5],
-            health: HEALTH_BY_TYPE[5],
-            attack_damage: ATTACK_BY_TYPE[5],
-            state: 0
-        });
-        run.slots[6] = Some(CharacterInfo {
-            id: 6,
-            alignment: 1,
-            character_type: 6,
-            cooldown: COOLDOWN_BY_TYPE[6],
-            cooldown_timer: COOLDOWN_BY_TYPE[6],
-            max_health: HEALTH_BY_TYPE[6],
-            health: HEALTH_BY_TYPE[6],
-            attack_damage: ATTACK_BY_TYPE[6],
-            state: 0
-        });
+        for (slot, hero_type) in lineup.iter().enumerate() {
+            run.slots[slot] = Some(CharacterInfo::new(slot as u16, 0, *hero_type));
+        }
+        for slot in HERO_TYPE_COUNT..CHARACTER_TYPE_COUNT {
+            run.slots[slot] = Some(run.new_enemy(slot as u16, slot as u8));
+        }
 
         run.last_character_id = 6;
 
@@ -203,325 +778,397 @@ pub mod extracto_program {
         let player = &ctx.accounts.player;
         let thread = &ctx.accounts.thread;
         let thread_authority = &ctx.accounts.thread_authority;
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        let season = &ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let run_record = &mut ctx.accounts.run_record;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let system_program = &ctx.accounts.system_program;
+        let now = Clock::get()?.unix_timestamp;
 
-        // Delete thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
-            clockwork_program.to_account_info(),
-            clockwork_sdk::cpi::ThreadDelete {
-                authority: thread_authority.to_account_info(),
-                close_to: player.to_account_info(),
-                thread: thread.to_account_info(),
-            },
-            &[&[THREAD_AUTHORITY_SEED, player.key().as_ref(), &[bump]]],
-        ))?;
-
-        player_data.runs_finished = player_data.runs_finished.checked_add(1).unwrap();
-
-        if run.score > player_data.best_score {
-            player_data.best_score = run.score;
+        // Delete thread via CPI. Cranked runs have none.
+        if run.thread != Pubkey::default() {
+            let thread = thread.as_ref().ok_or(GameErrorCode::WrongThread)?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadDelete {
+                    authority: thread_authority.to_account_info(),
+                    close_to: player.to_account_info(),
+                    thread: thread.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, player.key().as_ref(), &[bump]]],
+            ))?;
         }
 
-        player_data.is_in_run = false;
-        run.score = 0;
-        run.experience = 0;
-        run.last_character_id = 0;
-        run.last_card_id = 0;
+        // Return whatever is left of the crank bounties.
+        let vault_bump = *ctx
+            .bumps
+            .get("crank_vault")
+            .ok_or(GameErrorCode::MissingBump)?;
+        let run_key = run.key();
+        withdraw_from_crank_vault(
+            crank_vault,
+            player,
+            system_program,
+            &[CRANK_VAULT_SEED, run_key.as_ref(), &[vault_bump]],
+            crank_vault.lamports(),
+        )?;
+
+        run.skip_global_pause(&ctx.accounts.program_admin, now);
+        settle_run(run, now)?;
+        let end_reason = run.natural_end_reason();
+        finalize_run(
+            run,
+            player_data,
+            player_stats,
+            run_record,
+            leaderboard,
+            season,
+            season_leaderboard,
+            ctx.accounts.daily_leaderboard.as_deref_mut().map(|board| &mut **board),
+            end_reason,
+            now,
+        )?;
 
         Ok(())
     }
 
-    pub fn start_thread(ctx: Context<StartThread>, thread_id: Vec<u8>) -> Result<()> {
-        // Get accounts.
-        let system_program = &ctx.accounts.system_program;
+    pub fn expire_run(ctx: Context<ExpireRun>) -> Result<()> {
+        let run = &mut ctx.accounts.run;
+        let player_data = &mut ctx.accounts.player_data;
+        let player = &ctx.accounts.player;
+        let cleaner = &ctx.accounts.cleaner;
         let clockwork_program = &ctx.accounts.clockwork_program;
-        let user = &ctx.accounts.user;
-        let thread = &ctx.accounts.thread;
         let thread_authority = &ctx.accounts.thread_authority;
-        let run = &mut ctx.accounts.run;
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        let season = &ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let run_record = &mut ctx.accounts.run_record;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let system_program = &ctx.accounts.system_program;
+        let now = Clock::get()?.unix_timestamp;
 
-        // 1️⃣ Prepare an instruction to be automated.
-        let target_ix = Instruction {
-            program_id: ID,
-            accounts: crate::accounts::IncrementViaThread {
-                run: run.key(),
-                thread: thread.key(),
-                thread_authority: thread_authority.key(),
-            }
-            .to_account_metas(Some(true)),
-            data: crate::instruction::IncrementViaThread {}.data(),
-        };
+        // a global pause kept the player from acting, so it doesn't count towards the deadline
+        let last_action = run.expires_at.saturating_sub(RUN_INACTIVITY_TIMEOUT_SECONDS);
+        let deadline = run
+            .expires_at
+            .saturating_add(ctx.accounts.program_admin.paused_between(last_action, now));
+        require!(now >= deadline, GameErrorCode::RunNotExpired);
 
-        // 2️⃣ Define a trigger for the thread (every 10 secs).
-        let trigger = clockwork_sdk::state::Trigger::Cron {
-            schedule: "*/1 * * * * * *".into(),
-            skippable: true,
-        };
+        // The cleaner paid the rent for the run record, and for the stats if this created them;
+        // that comes back to them on top of the reward.
+        let mut rent_paid = run_record.to_account_info().lamports();
+        if player_stats.version == 0 {
+            rent_paid = rent_paid.saturating_add(player_stats.to_account_info().lamports());
+        }
 
-        // 3️⃣ Create thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_create(
-            CpiContext::new_with_signer(
+        // The thread's leftover lamports are pooled in the crank vault so the reward and the
+        // refund come out of one place.
+        if run.thread != Pubkey::default() {
+            let thread = ctx
+                .accounts
+                .thread
+                .as_ref()
+                .ok_or(GameErrorCode::WrongThread)?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
                 clockwork_program.to_account_info(),
-                clockwork_sdk::cpi::ThreadCreate {
-                    payer: user.to_account_info(),
-                    system_program: system_program.to_account_info(),
-                    thread: thread.to_account_info(),
+                clockwork_sdk::cpi::ThreadDelete {
                     authority: thread_authority.to_account_info(),
+                    close_to: crank_vault.to_account_info(),
+                    thread: thread.to_account_info(),
                 },
-                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]], //this is signer seeds needed by the called program to verify PDA signature
-            ),
-            LAMPORTS_PER_SOL,       // amount
-            thread_id,              // id
-            vec![target_ix.into()], // instructions
-            trigger,                // trigger
+                &[&[THREAD_AUTHORITY_SEED, run.authority.as_ref(), &[bump]]],
+            ))?;
+        }
+
+        let vault_bump = *ctx
+            .bumps
+            .get("crank_vault")
+            .ok_or(GameErrorCode::MissingBump)?;
+        let run_key = run.key();
+        let vault_seeds: &[&[u8]] = &[CRANK_VAULT_SEED, run_key.as_ref(), &[vault_bump]];
+        let payout = crank_vault
+            .lamports()
+            .min(rent_paid.saturating_add(CLEANUP_REWARD_LAMPORTS));
+        if payout > 0 {
+            withdraw_from_crank_vault(crank_vault, cleaner, system_program, vault_seeds, payout)?;
+        }
+        withdraw_from_crank_vault(
+            crank_vault,
+            player,
+            system_program,
+            vault_seeds,
+            crank_vault.lamports(),
+        )?;
+
+        // Nothing is simulated here: the end reason is fixed, and ticks the player never sat
+        // through shouldn't add to an abandoned run's score.
+        finalize_run(
+            run,
+            player_data,
+            player_stats,
+            run_record,
+            leaderboard,
+            season,
+            season_leaderboard,
+            ctx.accounts.daily_leaderboard.as_deref_mut().map(|board| &mut **board),
+            RunEndReason::Abandoned,
+            now,
         )?;
 
         Ok(())
     }
 
-    pub fn pause_thread(ctx: Context<PauseThread>) -> Result<()> {
-        // Get accounts.
-        let user = &ctx.accounts.user;
-        let clockwork_program = &ctx.accounts.clockwork_program;
+    pub fn increment_via_thread(ctx: Context<IncrementViaThread>) -> Result<ThreadResponse> {
+        let run = &mut ctx.accounts.run;
+        let player_data = &ctx.accounts.player_data;
         let thread = &ctx.accounts.thread;
         let thread_authority = &ctx.accounts.thread_authority;
+        let program_admin = &ctx.accounts.program_admin;
+        let now = Clock::get()?.unix_timestamp;
 
-        // 3️⃣ Pause thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_pause(
-            CpiContext::new_with_signer(
-                clockwork_program.to_account_info(),
-                clockwork_sdk::cpi::ThreadPause {
-                    thread: thread.to_account_info(),
-                    authority: thread_authority.to_account_info(),
-                },
-                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
-            ), // trigger
-        )?;
+        // While the program is paused game time stands still instead of piling up for catch-up.
+        if program_admin.paused {
+            run.last_tick_at = now;
+            run.tick_carry_ms = 0;
+            return Ok(ThreadResponse::default());
+        }
 
-        Ok(())
+        // The trigger is skippable, so a late execution makes up for the ones that were dropped.
+        run.skip_global_pause(program_admin, now);
+        catch_up(run, now)?;
+
+        // Let the client know before the thread runs dry and stops ticking.
+        let rent_floor = Rent::get()?.minimum_balance(thread.to_account_info().data_len());
+        let balance = thread.to_account_info().lamports().saturating_sub(rent_floor);
+        let fee = thread.fee.max(1);
+        if balance < fee.saturating_mul(THREAD_LOW_BALANCE_EXECUTIONS) {
+            emit!(ThreadBalanceLow {
+                run: run.key(),
+                thread: thread.key(),
+                balance,
+                executions_left: balance / fee,
+            });
+        }
+
+        // Game over: chain the auto-finish, which then closes the thread.
+        if run.heroes_alive() == 0 {
+            let auto_finish_ix = auto_finish_instruction(
+                run,
+                player_data,
+                thread.key(),
+                thread_authority.key(),
+            );
+            return Ok(ThreadResponse {
+                dynamic_instruction: Some(auto_finish_ix.into()),
+                ..ThreadResponse::default()
+            });
+        }
+
+        // Retune the schedule once the run leaves the early waves.
+        let interval = run.thread_interval_seconds();
+        if interval != run.thread_interval {
+            run.thread_interval = interval;
+            return Ok(ThreadResponse {
+                trigger: Some(thread_trigger(interval)),
+                ..ThreadResponse::default()
+            });
+        }
+
+        Ok(ThreadResponse::default())
     }
 
-    pub fn resume_thread(ctx: Context<ResumeThread>) -> Result<()> {
-        // Get accounts.
-        let user = &ctx.accounts.user;
-        let clockwork_program = &ctx.accounts.clockwork_program;
-        let thread = &ctx.accounts.thread;
-        let thread_authority = &ctx.accounts.thread_authority;
+    pub fn auto_finish_run(ctx: Context<AutoFinishRun>) -> Result<ThreadResponse> {
+        let run = &mut ctx.accounts.run;
+        let player_data = &mut ctx.accounts.player_data;
+        let player = &ctx.accounts.player;
+        let leaderboard = &mut ctx.accounts.leaderboard;
+        let season = &ctx.accounts.season;
+        let season_leaderboard = &mut ctx.accounts.season_leaderboard;
+        let run_record = &ctx.accounts.run_record;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let payer = &ctx.accounts.payer;
+        let system_program = &ctx.accounts.system_program;
+        let now = Clock::get()?.unix_timestamp;
 
-        // 3️⃣ Pause thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_resume(
-            CpiContext::new_with_signer(
-                clockwork_program.to_account_info(),
-                clockwork_sdk::cpi::ThreadResume {
-                    thread: thread.to_account_info(),
-                    authority: thread_authority.to_account_info(),
-                },
-                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
-            ), // trigger
+        // The record address was derived from `runs_finished` when this was chained. If the player
+        // has finished another run since, it names that run's record and could never be created, so
+        // the auto-finish is chained again with the current one instead of leaving the thread stuck.
+        let run_index = player_data.runs_finished.to_le_bytes();
+        let (record_address, record_bump) = Pubkey::find_program_address(
+            &[RUN_RECORD_SEED, run.authority.as_ref(), run_index.as_ref()],
+            &ID,
+        );
+        if run_record.key() != record_address {
+            let auto_finish_ix = auto_finish_instruction(
+                run,
+                player_data,
+                ctx.accounts.thread.key(),
+                ctx.accounts.thread_authority.key(),
+            );
+            return Ok(ThreadResponse {
+                dynamic_instruction: Some(auto_finish_ix.into()),
+                ..ThreadResponse::default()
+            });
+        }
+        let authority = run.authority;
+        create_program_account(
+            run_record,
+            payer,
+            system_program,
+            RunRecord::SPACE,
+            &[RUN_RECORD_SEED, authority.as_ref(), run_index.as_ref(), &[record_bump]],
         )?;
+        let mut run_record = Account::<RunRecord>::try_from_unchecked(run_record)?;
 
-        Ok(())
+        let vault_bump = *ctx
+            .bumps
+            .get("crank_vault")
+            .ok_or(GameErrorCode::MissingBump)?;
+        let run_key = run.key();
+        withdraw_from_crank_vault(
+            crank_vault,
+            player,
+            system_program,
+            &[CRANK_VAULT_SEED, run_key.as_ref(), &[vault_bump]],
+            crank_vault.lamports(),
+        )?;
+
+        settle_run(run, now)?;
+        let end_reason = run.natural_end_reason();
+        finalize_run(
+            run,
+            player_data,
+            player_stats,
+            &mut run_record,
+            leaderboard,
+            season,
+            season_leaderboard,
+            ctx.accounts.daily_leaderboard.as_deref_mut().map(|board| &mut **board),
+            end_reason,
+            now,
+        )?;
+        run_record.exit(&ID)?;
+
+        // The thread can't delete itself via CPI, so it closes through its response instead.
+        Ok(ThreadResponse {
+            close_to: Some(player.key()),
+            ..ThreadResponse::default()
+        })
     }
 
-    pub fn delete_thread(ctx: Context<DeleteThread>) -> Result<()> {
-        // Get accounts
-        let clockwork_program = &ctx.accounts.clockwork_program;
-        let user = &ctx.accounts.user;
-        let thread = &ctx.accounts.thread;
-        let thread_authority = &ctx.accounts.thread_authority;
+    pub fn tick(ctx: Context<Tick>) -> Result<()> {
+        let run = &mut ctx.accounts.run;
+        let cranker = &ctx.accounts.cranker;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let system_program = &ctx.accounts.system_program;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Ticking is refused during a pause; afterwards the pause is skipped rather than caught up.
+        run.skip_global_pause(&ctx.accounts.program_admin, now);
+        let processed = catch_up(run, now)?;
+        require!(processed > 0, GameErrorCode::NoTicksDue);
+
+        // The bounty is optional: an empty vault just means cranking for free.
+        let available = crank_vault
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(0));
+        if available >= CRANK_BOUNTY_LAMPORTS {
+            let vault_bump = *ctx
+                .bumps
+                .get("crank_vault")
+                .ok_or(GameErrorCode::MissingBump)?;
+            let run_key = run.key();
+            withdraw_from_crank_vault(
+                crank_vault,
+                cranker,
+                system_program,
+                &[CRANK_VAULT_SEED, run_key.as_ref(), &[vault_bump]],
+                CRANK_BOUNTY_LAMPORTS,
+            )?;
+        }
 
-        // Delete thread via CPI.
-        let bump = *ctx.bumps.get("thread_authority").unwrap();
-        clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
-            clockwork_program.to_account_info(),
-            clockwork_sdk::cpi::ThreadDelete {
-                authority: thread_authority.to_account_info(),
-                close_to: user.to_account_info(),
-                thread: thread.to_account_info(),
-            },
-            &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
-        ))?;
         Ok(())
     }
 
-    pub fn increment_via_thread(ctx: Context<IncrementViaThread>) -> Result<()> {
-        let run = &mut ctx.accounts.run;
-        run.score = run.score.checked_add(1).unwrap();
-
-        let mut slots_clone = run.slots.clone();
-        let n = run.slots.len();
-
-        //refresh cooldowns
-        for i in 0..n {
-            let slot = slots_clone[i];
-
-            match slot {
-                Some(mut character_info) => {
-                    //cooldowns
-                    let mut perform_action = false;
-                    let mut new_cooldown_timer = character_info.cooldown_timer - 1;
-                    if new_cooldown_timer == 0 {
-                        new_cooldown_timer = character_info.cooldown;
-                        perform_action = true;
-                    }
-                    else{
-                        character_info.state = 0;
-                    }
-                    character_info.cooldown_timer = new_cooldown_timer;
-
-                    slots_clone[i] = Some(character_info);
-
-                    if perform_action {
-                        //perform actions for zombies
-                        if character_info.alignment == 1 {
-                            if i > 0 {
-                                if slots_clone[i - 1].is_none() {
-                                    //move left
-                                    slots_clone[i] = None;
-                                    character_info.state = 2;
-                                    slots_clone[i - 1] = Some(character_info);
-                                }
-                                //if there is somebody to the left
-                                else {
-                                    let mut attacked_character = slots_clone[i - 1].unwrap();
+    pub fn fund_crank_vault(ctx: Context<FundCrankVault>, amount: u64) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let crank_vault = &ctx.accounts.crank_vault;
+        let system_program = &ctx.accounts.system_program;
 
-                                    //if it is a hero
-                                    if attacked_character.alignment == 0 {
-                                        character_info.state = 1;
-                                        slots_clone[i] = Some(character_info);
-                                        //attack the hero
-                                        if character_info.attack_damage >= attacked_character.health
-                                        {
-                                            slots_clone[i - 1] = None;
-                                        } else {
-                                            let new_health = attacked_character.health
-                                                - character_info.attack_damage;
-                                            attacked_character.health = new_health;
-                                            slots_clone[i - 1] = Some(attacked_character);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        //perform actions for heroes
-                        else if character_info.alignment == 0 {
-                            match character_info.character_type {
-                                2 => {
-                                    if slots_clone[i + 1].is_none() {
-                                    }
-                                    //if there is somebody to the left
-                                    else {
-                                        let mut attacked_character = slots_clone[i + 1].unwrap();
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: player.to_account_info(),
+                    to: crank_vault.to_account_info(),
+                },
+            ),
+            amount,
+        )
+    }
 
-                                        //if it is a hero
-                                        if attacked_character.alignment == 1 {
-                                            //attack the hero
-                                            character_info.state = 1;
-                                            slots_clone[i] = Some(character_info);
-                                            if character_info.attack_damage
-                                                >= attacked_character.health
-                                            {
-                                                slots_clone[i + 1] = None;
-                                                let new_experience = run.experience + 1;
-                                                run.experience = new_experience;
-                                            } else {
-                                                let new_health = attacked_character.health
-                                                    - character_info.attack_damage;
-                                                attacked_character.health = new_health;
-                                                slots_clone[i + 1] = Some(attacked_character);
-                                            }
-                                        }
-                                    }
-                                }
-                                1 => {
-                                    for a in 4..7 {
-                                        if slots_clone[a].is_none() {
-                                            //nobody to shoot
-                                        } else {
-                                            let mut attacked_character = slots_clone[a].unwrap();
-
-                                            //if it is a hero
-                                            if attacked_character.alignment == 1 {
-                                                //attack the hero
-                                                character_info.state = 1;
-                                                slots_clone[i] = Some(character_info);
-                                                if character_info.attack_damage
-                                                    >= attacked_character.health
-                                                {
-                                                    slots_clone[a] = None;
-                                                    let new_experience = run.experience + 1;
-                                                    run.experience = new_experience;
-                                                } else {
-                                                    let new_health = attacked_character.health
-                                                        - character_info.attack_damage;
-                                                    attacked_character.health = new_health;
-                                                    slots_clone[a] = Some(attacked_character);
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                0 => {
-                                    if slots_clone[6].is_none() {
-                                    } else {
-                                        let mut attacked_character = slots_clone[6].unwrap();
+    pub fn top_up_run_thread(ctx: Context<TopUpRunThread>, amount: u64) -> Result<()> {
+        let player = &ctx.accounts.player;
+        let thread = &ctx.accounts.thread;
+        let system_program = &ctx.accounts.system_program;
 
-                                        if attacked_character.alignment == 1 {
-                                            //attack
-                                            character_info.state = 1;
-                                            slots_clone[i] = Some(character_info);
-                                            if character_info.attack_damage
-                                                >= attacked_character.health
-                                            {
-                                                slots_clone[6] = None;
-                                                let new_experience = run.experience + 1;
-                                                run.experience = new_experience;
-                                            } else {
-                                                let new_health = attacked_character.health
-                                                    - character_info.attack_damage;
-                                                attacked_character.health = new_health;
-                                                slots_clone[6] = Some(attacked_character);
-                                            }
-                                        }
-                                    }
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: player.to_account_info(),
+                    to: thread.to_account_info(),
+                },
+            ),
+            amount,
+        )
+    }
 
-        if slots_clone[6].is_none() {
-            let slot = Clock::get()?.slot;
-            let xorshift_output = xorshift64(slot);
-            let random_enemy_type_offset = xorshift_output % (4);
-            let random_enemy_type = (random_enemy_type_offset + 3) as u8;
-
-            let new_last_character_id = run.last_character_id + 1;
-            run.last_character_id = new_last_character_id;
-
-            let new_character_info = CharacterInfo {
-                id: new_last_character_id,
-                alignment: 1,
-                character_type: random_enemy_type,
-                cooldown: COOLDOWN_BY_TYPE[random_enemy_type as usize],
-                cooldown_timer: COOLDOWN_BY_TYPE[random_enemy_type as usize],
-                max_health: HEALTH_BY_TYPE[random_enemy_type as usize],
-                health: HEALTH_BY_TYPE[random_enemy_type as usize],
-                attack_damage: ATTACK_BY_TYPE[random_enemy_type as usize],
-                state: 0
-            };
+    #[session_auth_or(
+        ctx.accounts.run.authority.key() == ctx.accounts.user.key(),
+        GameErrorCode::WrongAuthority
+    )]
+    pub fn pause_run(ctx: Context<PauseRun>) -> Result<()> {
+        let run = &mut ctx.accounts.run;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread_authority = &ctx.accounts.thread_authority;
 
-            slots_clone[6] = Some(new_character_info);
+        if run.thread != Pubkey::default() {
+            let thread = ctx
+                .accounts
+                .thread
+                .as_ref()
+                .ok_or(GameErrorCode::WrongThread)?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_pause(CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadPause {
+                    thread: thread.to_account_info(),
+                    authority: thread_authority.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, run.authority.as_ref(), &[bump]]],
+            ))?;
         }
 
-        run.slots = slots_clone;
+        let now = Clock::get()?.unix_timestamp;
+        run.status = RunStatus::Paused;
+        run.paused_at = now;
+        run.refresh_deadline(now);
 
         Ok(())
     }
@@ -530,9 +1177,44 @@ pub mod extracto_program {
         ctx.accounts.run.authority.key() == ctx.accounts.user.key(),
         GameErrorCode::WrongAuthority
     )]
-    pub fn increment(ctx: Context<Increment>) -> Result<()> {
+    pub fn resume_run(ctx: Context<ResumeRun>) -> Result<()> {
         let run = &mut ctx.accounts.run;
-        run.score = run.score.checked_add(1).unwrap();
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread_authority = &ctx.accounts.thread_authority;
+        let now = Clock::get()?.unix_timestamp;
+
+        if run.thread != Pubkey::default() {
+            let thread = ctx
+                .accounts
+                .thread
+                .as_ref()
+                .ok_or(GameErrorCode::WrongThread)?;
+            let bump = *ctx
+                .bumps
+                .get("thread_authority")
+                .ok_or(GameErrorCode::MissingBump)?;
+            clockwork_sdk::cpi::thread_resume(CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadResume {
+                    thread: thread.to_account_info(),
+                    authority: thread_authority.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, run.authority.as_ref(), &[bump]]],
+            ))?;
+        }
+
+        // Only the part of the pause still covered by the budget is frozen; the rest is
+        // caught up as ordinary game time.
+        let frozen = now
+            .saturating_sub(run.paused_at)
+            .min(MAX_PAUSE_SECONDS_PER_RUN.saturating_sub(run.pause_used))
+            .max(0);
+        run.pause_used = run.pause_used.saturating_add(frozen);
+        run.last_tick_at = run.last_tick_at.saturating_add(frozen);
+        run.status = RunStatus::Active;
+        run.paused_at = 0;
+        run.refresh_deadline(now);
+
         Ok(())
     }
 
@@ -543,44 +1225,63 @@ pub mod extracto_program {
     pub fn upgrade(ctx: Context<Upgrade>, card_slot: u16, character_slot_index: u8) -> Result<()> {
         let run = &mut ctx.accounts.run;
 
-        let card_info = run.cards[card_slot as usize];
+        let card_info = *run
+            .cards
+            .get(card_slot as usize)
+            .ok_or(GameErrorCode::InvalidCardSlot)?;
+        run.pay_for_card(card_info.card_type)?;
 
-        let mut character_info = run.slots[character_slot_index as usize].unwrap();
+        let mut character_info = run
+            .slots
+            .get(character_slot_index as usize)
+            .ok_or(GameErrorCode::InvalidCharacterSlot)?
+            .ok_or(GameErrorCode::EmptySlot)?;
 
         match card_info.card_type {
             0 => {
-                let new_max_health = character_info.max_health + 10;
+                let new_max_health = character_info
+                    .max_health
+                    .checked_add(10)
+                    .ok_or(GameErrorCode::StatOverflow)?;
                 character_info.max_health = new_max_health;
-                let new_health = character_info.health + 10;
-                character_info.health = new_health;
+                // with no healing the card only raises the cap
+                if !run.has_mutator(MUTATOR_NO_HEALING) {
+                    let new_health = character_info
+                        .health
+                        .checked_add(10)
+                        .ok_or(GameErrorCode::StatOverflow)?;
+                    character_info.health = new_health;
+                }
                 run.slots[character_slot_index as usize] = Some(character_info);
             }
             1 => {
-                let new_attack_damage = character_info.attack_damage + 1;
+                let new_attack_damage = character_info
+                    .attack_damage
+                    .checked_add(1)
+                    .ok_or(GameErrorCode::StatOverflow)?;
                 character_info.attack_damage = new_attack_damage;
                 run.slots[character_slot_index as usize] = Some(character_info);
             }
             2 => {
-                let new_cooldown = character_info.cooldown - 1;
-                if new_cooldown > 0 {
-                    character_info.cooldown = new_cooldown;
+                //cooldown never goes below 1
+                if character_info.cooldown > 1 {
+                    character_info.cooldown = character_info.cooldown.saturating_sub(1);
                     run.slots[character_slot_index as usize] = Some(character_info);
                 }
             }
             _ => {}
         }
 
-        let new_last_card_id = run.last_card_id + 1;
-        run.last_card_id = new_last_card_id;
+        run.cards_played_by_type[card_info.card_type as usize] =
+            run.cards_played_by_type[card_info.card_type as usize].saturating_add(1);
 
-        if run.experience >= CARD_COST_BY_TYPE[card_info.card_type as usize] as u16 {
-            let new_experience =
-                run.experience - CARD_COST_BY_TYPE[card_info.card_type as usize] as u16;
-            run.experience = new_experience;
-        }
+        run.refresh_deadline(Clock::get()?.unix_timestamp);
 
-        let slot = Clock::get()?.slot;
-        let xorshift_output = xorshift64(slot);
+        // ids only need to be unique among the cards in hand
+        let new_last_card_id = run.last_card_id.wrapping_add(1);
+        run.last_card_id = new_last_card_id;
+
+        let xorshift_output = run.next_random();
         let random_card_type = xorshift_output % (3);
 
         run.cards[card_slot as usize] = CardInfo {
@@ -592,233 +1293,1272 @@ pub mod extracto_program {
 
         // if (run.experience >= run.cards.)
 
-        run.score = run.score.checked_add(100).unwrap();
-
         Ok(())
     }
 
-    pub fn reset(ctx: Context<Reset>) -> Result<()> {
-        let run = &mut ctx.accounts.run;
-        run.score = 0;
-        Ok(())
+    /// Reached for any instruction not defined above; only the tutorial ones in
+    /// `DEBUG_INSTRUCTIONS` are handled, and only by builds with `debug-instructions`.
+    pub fn fallback(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> Result<()> {
+        require!(
+            data.len() >= 8,
+            anchor_lang::error::ErrorCode::InstructionMissing
+        );
+        let name = debug_instruction_name(&data[..8])
+            .ok_or(anchor_lang::error::ErrorCode::InstructionFallbackNotFound)?;
+        debug_instructions::dispatch(name, program_id, accounts, &data[8..])
     }
 }
 
-#[derive(Accounts)]
-#[instruction(player_name: String)]
-pub struct InitPlayer<'info> {
-    #[account(
-        init,
-        payer = player,
-        seeds = [PLAYER_SEED, player.key().as_ref()],
-        bump,
-        space = 8 + 32 + 4 + player_name.len() + 4 + 8 + 1)]
-    pub player_data: Account<'info, PlayerData>,
-    #[account(
-        init,
-        payer = player,
-        seeds = [RUN_SEED, player.key().as_ref()],
-        bump,
-        space = 8 + 32 + 8 + 2 + 77 + 2 + 9 + 2)]
-    pub run: Account<'info, RunData>,
-    #[account(mut)]
-    pub player: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-#[instruction(thread_id: Vec<u8>)]
-pub struct StartNewRun<'info> {
-    #[account(mut, seeds = [RUN_SEED, player.key().as_ref()], bump)]
-    pub run: Account<'info, RunData>,
-
-    #[account(mut, seeds = [PLAYER_SEED, player.key().as_ref()], bump)]
-    pub player_data: Account<'info, PlayerData>,
-
-    #[account(mut)]
-    pub player: Signer<'info>,
+/// The tutorial instructions, dispatched through `extracto_program::fallback` the same way Anchor
+/// dispatches the ones in the program module.
+#[cfg(feature = "debug-instructions")]
+pub mod debug_instructions {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
 
-    #[account(address = clockwork_sdk::ID)]
-    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+    pub fn dispatch(
+        name: &str,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        ix_data: &[u8],
+    ) -> Result<()> {
+        macro_rules! run {
+            ($accounts:ident, $handler:ident $(, $arg:ident)*) => {{
+                let mut remaining_accounts = accounts;
+                let mut bumps = BTreeMap::new();
+                let mut ctx_accounts = $accounts::try_accounts(
+                    program_id,
+                    &mut remaining_accounts,
+                    ix_data,
+                    &mut bumps,
+                    &mut BTreeSet::new(),
+                )?;
+                $handler(
+                    Context::new(program_id, &mut ctx_accounts, remaining_accounts, bumps)
+                    $(, $arg)*
+                )?;
+                ctx_accounts.exit(program_id)
+            }};
+        }
 
-    #[account(address = system_program::ID)]
-    pub system_program: Program<'info, System>,
+        msg!("Instruction: {}", name);
+        match name {
+            "increment" => run!(Increment, increment),
+            "reset" => run!(Reset, reset),
+            "start_thread" => {
+                let thread_id = Vec::<u8>::deserialize(&mut &ix_data[..])
+                    .map_err(|_| anchor_lang::error::ErrorCode::InstructionDidNotDeserialize)?;
+                run!(StartThread, start_thread, thread_id)
+            }
+            "pause_thread" => run!(PauseThread, pause_thread),
+            "resume_thread" => run!(ResumeThread, resume_thread),
+            "delete_thread" => run!(DeleteThread, delete_thread),
+            _ => Err(anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into()),
+        }
+    }
 
-    #[account(mut, address = Thread::pubkey(thread_authority.key(), thread_id))]
-    pub thread: SystemAccount<'info>,
+    #[session_auth_or(
+        ctx.accounts.run.authority.key() == ctx.accounts.user.key(),
+        GameErrorCode::WrongAuthority
+    )]
+    pub fn increment(ctx: Context<Increment>) -> Result<()> {
+        // Steps the game by hand; score still only comes from what the tick observes.
+        let run = &mut ctx.accounts.run;
+        process_tick(run)?;
+        Ok(())
+    }
 
-    #[account(seeds = [THREAD_AUTHORITY_SEED, player.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
-}
+    #[derive(Accounts, Session)]
+    pub struct Increment<'info> {
+        #[account(
+            mut,
+            seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+            bump,
+            constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+            constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+        pub run: Account<'info, RunData>,
 
-#[derive(Accounts)]
-#[instruction(thread_id: Vec<u8>)]
-pub struct StartThread<'info> {
-    #[account(mut)]
-    pub run: Account<'info, RunData>,
+        pub user: Signer<'info>,
 
-    /// The Clockwork thread program.
-    #[account(address = clockwork_sdk::ID)]
-    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+        #[session(
+            // The ephemeral keypair signing the transaction
+            signer = user,
+            // The authority of the user account which must have created the session
+            authority = run.authority.key()
+        )]
+        // Session Tokens are passed as optional accounts
+        pub session_token: Option<Account<'info, SessionToken>>,
+    }
 
-    /// The signer who will pay to initialize the program.
-    /// (not to be confused with the thread executions).
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub fn reset(ctx: Context<Reset>) -> Result<()> {
+        let run = &mut ctx.accounts.run;
+        run.score = 0;
+        run.score_breakdown = ScoreBreakdown::default();
+        Ok(())
+    }
 
-    /// The Solana system program.
-    #[account(address = system_program::ID)]
-    pub system_program: Program<'info, System>,
+    #[derive(Accounts)]
+    pub struct Reset<'info> {
+        #[account(
+            mut,
+            seeds = [RUN_SEED, user.key().as_ref(), run_slot_seed(&run.run_slot)],
+            bump,
+            constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+        pub run: Account<'info, RunData>,
+        pub user: Signer<'info>,
+    }
 
-    /// Address to assign to the newly created thread.
-    #[account(mut, address = Thread::pubkey(thread_authority.key(), thread_id))]
-    pub thread: SystemAccount<'info>,
+    pub fn start_thread(ctx: Context<StartThread>, thread_id: Vec<u8>) -> Result<()> {
+        // Get accounts.
+        let system_program = &ctx.accounts.system_program;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let user = &ctx.accounts.user;
+        let thread = &ctx.accounts.thread;
+        let thread_authority = &ctx.accounts.thread_authority;
+        let run = &mut ctx.accounts.run;
 
-    /// The pda that will own and manage the thread.
-    #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
+        // 1️⃣ Prepare an instruction to be automated.
+        let target_ix = Instruction {
+            program_id: ID,
+            accounts: crate::accounts::IncrementViaThread {
+                run: run.key(),
+                player_data: Pubkey::find_program_address(
+                    &[PLAYER_SEED, run.authority.as_ref()],
+                    &ID,
+                )
+                .0,
+                thread: thread.key(),
+                thread_authority: thread_authority.key(),
+                program_admin: Pubkey::find_program_address(&[PROGRAM_ADMIN_SEED], &ID).0,
+            }
+            .to_account_metas(Some(true)),
+            data: crate::instruction::IncrementViaThread {}.data(),
+        };
+
+        // 2️⃣ Define a trigger for the thread (every 10 secs).
+        let trigger = clockwork_sdk::state::Trigger::Cron {
+            schedule: "*/1 * * * * * *".into(),
+            skippable: true,
+        };
+
+        // 3️⃣ Create thread via CPI.
+        let funding = thread_funding_lamports(DEFAULT_EXPECTED_RUN_WAVES, run.difficulty)?;
+        let bump = *ctx
+            .bumps
+            .get("thread_authority")
+            .ok_or(GameErrorCode::MissingBump)?;
+        clockwork_sdk::cpi::thread_create(
+            CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadCreate {
+                    payer: user.to_account_info(),
+                    system_program: system_program.to_account_info(),
+                    thread: thread.to_account_info(),
+                    authority: thread_authority.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]], //this is signer seeds needed by the called program to verify PDA signature
+            ),
+            funding,                // amount
+            thread_id,              // id
+            vec![target_ix.into()], // instructions
+            trigger,                // trigger
+        )?;
+
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    #[instruction(thread_id: Vec<u8>)]
+    pub struct StartThread<'info> {
+        #[account(
+            mut,
+            constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+        pub run: Account<'info, RunData>,
+
+        /// The Clockwork thread program.
+        #[account(address = clockwork_sdk::ID)]
+        pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+        /// The signer who will pay to initialize the program.
+        /// (not to be confused with the thread executions).
+        #[account(mut)]
+        pub user: Signer<'info>,
+
+        /// The Solana system program.
+        #[account(address = system_program::ID)]
+        pub system_program: Program<'info, System>,
+
+        /// Address to assign to the newly created thread.
+        #[account(mut, address = Thread::pubkey(thread_authority.key(), thread_id))]
+        pub thread: SystemAccount<'info>,
+
+        /// The pda that will own and manage the thread.
+        #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
+        pub thread_authority: SystemAccount<'info>,
+    }
+
+    pub fn pause_thread(ctx: Context<PauseThread>) -> Result<()> {
+        // Get accounts.
+        let user = &ctx.accounts.user;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread = &ctx.accounts.thread;
+        let thread_authority = &ctx.accounts.thread_authority;
+
+        // 3️⃣ Pause thread via CPI.
+        let bump = *ctx
+            .bumps
+            .get("thread_authority")
+            .ok_or(GameErrorCode::MissingBump)?;
+        clockwork_sdk::cpi::thread_pause(
+            CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadPause {
+                    thread: thread.to_account_info(),
+                    authority: thread_authority.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
+            ), // trigger
+        )?;
+
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct PauseThread<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        /// The Clockwork thread program.
+        #[account(address = clockwork_sdk::ID)]
+        pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+        /// The thread to pause.
+        #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
+        pub thread: Account<'info, Thread>,
+
+        /// The pda that will own and manage the thread.
+        #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
+        pub thread_authority: SystemAccount<'info>,
+    }
+
+    pub fn resume_thread(ctx: Context<ResumeThread>) -> Result<()> {
+        // Get accounts.
+        let user = &ctx.accounts.user;
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let thread = &ctx.accounts.thread;
+        let thread_authority = &ctx.accounts.thread_authority;
+
+        // 3️⃣ Pause thread via CPI.
+        let bump = *ctx
+            .bumps
+            .get("thread_authority")
+            .ok_or(GameErrorCode::MissingBump)?;
+        clockwork_sdk::cpi::thread_resume(
+            CpiContext::new_with_signer(
+                clockwork_program.to_account_info(),
+                clockwork_sdk::cpi::ThreadResume {
+                    thread: thread.to_account_info(),
+                    authority: thread_authority.to_account_info(),
+                },
+                &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
+            ), // trigger
+        )?;
+
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ResumeThread<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+        /// The Clockwork thread program.
+        #[account(address = clockwork_sdk::ID)]
+        pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+        /// The thread to reset.
+        #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
+        pub thread: Account<'info, Thread>,
+
+        /// The pda that will own and manage the thread.
+        #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
+        pub thread_authority: SystemAccount<'info>,
+    }
+
+    pub fn delete_thread(ctx: Context<DeleteThread>) -> Result<()> {
+        // Get accounts
+        let clockwork_program = &ctx.accounts.clockwork_program;
+        let user = &ctx.accounts.user;
+        let thread = &ctx.accounts.thread;
+        let thread_authority = &ctx.accounts.thread_authority;
+
+        // Delete thread via CPI.
+        let bump = *ctx
+            .bumps
+            .get("thread_authority")
+            .ok_or(GameErrorCode::MissingBump)?;
+        clockwork_sdk::cpi::thread_delete(CpiContext::new_with_signer(
+            clockwork_program.to_account_info(),
+            clockwork_sdk::cpi::ThreadDelete {
+                authority: thread_authority.to_account_info(),
+                close_to: user.to_account_info(),
+                thread: thread.to_account_info(),
+            },
+            &[&[THREAD_AUTHORITY_SEED, user.key().as_ref(), &[bump]]],
+        ))?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct DeleteThread<'info> {
+        #[account(mut)]
+        pub user: Signer<'info>,
+
+        /// The Clockwork thread program.
+        #[account(address = clockwork_sdk::ID)]
+        pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+        /// The thread to reset.
+        #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
+        pub thread: Account<'info, Thread>,
+
+        /// The pda that owns and manages the thread.
+        #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
+        pub thread_authority: SystemAccount<'info>,
+    }
+}
+
+#[cfg(not(feature = "debug-instructions"))]
+pub mod debug_instructions {
+    use super::*;
+
+    pub fn dispatch(
+        _name: &str,
+        _program_id: &Pubkey,
+        _accounts: &[AccountInfo],
+        _ix_data: &[u8],
+    ) -> Result<()> {
+        err!(GameErrorCode::DebugInstructionsDisabled)
+    }
 }
 
 #[derive(Accounts)]
-pub struct FinishRun<'info> {
-    #[account(mut, seeds = [RUN_SEED, player.key().as_ref()], bump)]
-    pub run: Account<'info, RunData>,
+#[instruction(difficulty: Difficulty)]
+pub struct InitLeaderboard<'info> {
+    #[account(
+        init,
+        payer = payer,
+        seeds = [LEADERBOARD_SEED, difficulty.seed()],
+        bump,
+        space = Leaderboard::SPACE)]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-    #[account(mut, seeds = [PLAYER_SEED, player.key().as_ref()], bump)]
-    pub player_data: Account<'info, PlayerData>,
+#[derive(Accounts)]
+pub struct InitProgramAdmin<'info> {
+    #[account(
+        init,
+        payer = authority,
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        space = ProgramAdmin::SPACE)]
+    pub program_admin: Account<'info, ProgramAdmin>,
 
     #[account(mut)]
-    pub player: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    #[account(address = clockwork_sdk::ID)]
-    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::ExtractoProgram>,
 
-    #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
-    pub thread: Account<'info, Thread>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ GameErrorCode::WrongAuthority)]
+    pub program_data: Account<'info, ProgramData>,
 
-    #[account(seeds = [THREAD_AUTHORITY_SEED, player.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DeleteThread<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+pub struct ProposeAdmin<'info> {
+    #[account(mut, seeds = [PROGRAM_ADMIN_SEED], bump, has_one = admin @ GameErrorCode::WrongAuthority)]
+    pub program_admin: Account<'info, ProgramAdmin>,
 
-    /// The Clockwork thread program.
-    #[account(address = clockwork_sdk::ID)]
-    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+    pub admin: Signer<'info>,
+}
 
-    /// The thread to reset.
-    #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
-    pub thread: Account<'info, Thread>,
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        constraint = program_admin.pending_admin == pending_admin.key() @ GameErrorCode::NotPendingAdmin)]
+    pub program_admin: Account<'info, ProgramAdmin>,
 
-    /// The pda that owns and manages the thread.
-    #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
+    pub pending_admin: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct PauseThread<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    /// The Clockwork thread program.
-    #[account(address = clockwork_sdk::ID)]
-    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
-
-    /// The thread to pause.
-    #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
-    pub thread: Account<'info, Thread>,
+pub struct SetPaused<'info> {
+    #[account(mut, seeds = [PROGRAM_ADMIN_SEED], bump, has_one = admin @ GameErrorCode::WrongAuthority)]
+    pub program_admin: Account<'info, ProgramAdmin>,
 
-    /// The pda that will own and manage the thread.
-    #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
+    pub admin: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ResumeThread<'info> {
+pub struct ForceFinishRun<'info> {
+    #[account(seeds = [PROGRAM_ADMIN_SEED], bump, has_one = admin @ GameErrorCode::WrongAuthority)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    /// Pays for the run record, receives nothing.
     #[account(mut)]
-    pub user: Signer<'info>,
-    /// The Clockwork thread program.
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Idle @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, run.authority.key().as_ref()],
+        bump,
+        constraint = player_data.is_run_active(run.run_slot) @ GameErrorCode::NotInRun,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    #[account(mut, address = run.authority)]
+    pub player: SystemAccount<'info>,
+
     #[account(address = clockwork_sdk::ID)]
     pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
 
-    /// The thread to reset.
-    #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
-    pub thread: Account<'info, Thread>,
+    /// Required when the run has a thread, omitted for cranked runs.
+    #[account(
+        mut,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
 
-    /// The pda that will own and manage the thread.
-    #[account(seeds = [THREAD_AUTHORITY_SEED, user.key().as_ref()], bump)]
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
     pub thread_authority: SystemAccount<'info>,
+
+    #[account(mut, seeds = [LEADERBOARD_SEED, run.difficulty.seed()], bump)]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+
+    #[account(seeds = [SEASON_SEED], bump)]
+    pub season: Account<'info, Season>,
+
+    #[account(mut, seeds = [SEASON_SEED, LEADERBOARD_SEED], bump)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+
+    /// Required when the run played a daily challenge.
+    #[account(
+        mut,
+        seeds = [DAILY_CHALLENGE_SEED, run.daily_day.to_le_bytes().as_ref(), LEADERBOARD_SEED],
+        bump)]
+    pub daily_leaderboard: Option<Box<Account<'info, Leaderboard>>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [RUN_RECORD_SEED, run.authority.key().as_ref(), player_data.runs_finished.to_le_bytes().as_ref()],
+        bump,
+        space = RunRecord::SPACE)]
+    pub run_record: Box<Account<'info, RunRecord>>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [PLAYER_STATS_SEED, run.authority.key().as_ref()],
+        bump,
+        space = PlayerStats::SPACE)]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct IncrementViaThread<'info> {
-    #[account(mut)]
-    pub run: Account<'info, RunData>,
+#[instruction(run_slot: u8)]
+pub struct MigrateRun<'info> {
+    /// CHECK: may be in a layout `Account<RunData>` can't read yet; ownership and seeds are
+    /// checked here and the discriminator when the handler deserializes it after growing it
+    #[account(
+        mut,
+        owner = ID,
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run_slot)],
+        bump)]
+    pub run: UncheckedAccount<'info>,
 
-    /// Verify that only this thread can execute the Increment Instruction
-    #[account(signer, constraint = thread.authority.eq(&thread_authority.key()))]
-    pub thread: Account<'info, Thread>,
+    #[account(mut)]
+    pub player: Signer<'info>,
 
-    /// The Thread Admin
-    /// The authority that was used as a seed to derive the thread address
-    /// `thread_authority` should equal `thread.thread_authority`
-    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
-    pub thread_authority: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts, Session)]
-pub struct Increment<'info> {
-    #[account(mut, seeds = [RUN_SEED, run.authority.key().as_ref()], bump)]
-    pub run: Account<'info, RunData>,
+#[derive(Accounts)]
+pub struct MigratePlayer<'info> {
+    /// CHECK: see `MigrateRun::run`
+    #[account(mut, owner = ID, seeds = [PLAYER_SEED, player.key().as_ref()], bump)]
+    pub player_data: UncheckedAccount<'info>,
 
-    pub user: Signer<'info>,
+    /// CHECK: see `MigrateRun::run`; omitted if the player has no stats yet
+    #[account(mut, owner = ID, seeds = [PLAYER_STATS_SEED, player.key().as_ref()], bump)]
+    pub player_stats: Option<UncheckedAccount<'info>>,
 
-    #[session(
-        // The ephemeral keypair signing the transaction
-        signer = user,
-        // The authority of the user account which must have created the session
-        authority = run.authority.key()
-    )]
-    // Session Tokens are passed as optional accounts
-    pub session_token: Option<Account<'info, SessionToken>>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts, Session)]
-#[instruction(card_id: u16, slot_id: u8)]
-pub struct Upgrade<'info> {
-    #[account(mut, seeds = [RUN_SEED, run.authority.key().as_ref()], bump)]
-    pub run: Account<'info, RunData>,
+#[derive(Accounts)]
+pub struct MigrateProgramAdmin<'info> {
+    /// CHECK: see `MigrateRun::run`; the admin is checked against it in the handler
+    #[account(mut, owner = ID, seeds = [PROGRAM_ADMIN_SEED], bump)]
+    pub program_admin: UncheckedAccount<'info>,
 
-    pub user: Signer<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
 
-    #[session(
-        // The ephemeral keypair signing the transaction
-        signer = user,
-        // The authority of the user account which must have created the session
-        authority = run.authority.key()
-    )]
-    // Session Tokens are passed as optional accounts
-    pub session_token: Option<Account<'info, SessionToken>>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Reset<'info> {
-    #[account(mut, seeds = [RUN_SEED, user.key().as_ref()], bump)]
-    pub run: Account<'info, RunData>,
-    pub user: Signer<'info>,
+#[instruction(day: u32)]
+pub struct InitDailyChallenge<'info> {
+    #[account(
+        init,
+        payer = payer,
+        seeds = [DAILY_CHALLENGE_SEED, day.to_le_bytes().as_ref()],
+        bump,
+        space = DailyChallenge::SPACE)]
+    pub daily_challenge: Account<'info, DailyChallenge>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [DAILY_CHALLENGE_SEED, day.to_le_bytes().as_ref(), LEADERBOARD_SEED],
+        bump,
+        space = Leaderboard::SPACE)]
+    pub daily_leaderboard: Box<Account<'info, Leaderboard>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
-#[account]
-pub struct RunData {
-    //32
-    pub authority: Pubkey,
+#[derive(Accounts)]
+pub struct InitSeason<'info> {
+    #[account(
+        init,
+        payer = admin,
+        seeds = [SEASON_SEED],
+        bump,
+        space = Season::SPACE)]
+    pub season: Account<'info, Season>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [SEASON_SEED, LEADERBOARD_SEED],
+        bump,
+        space = Leaderboard::SPACE)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+    /// The duration is fixed for every later season, so only the admin may pick it.
+    #[account(seeds = [PROGRAM_ADMIN_SEED], bump, has_one = admin @ GameErrorCode::WrongAuthority)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RolloverSeason<'info> {
+    #[account(mut, seeds = [SEASON_SEED], bump)]
+    pub season: Account<'info, Season>,
+    #[account(mut, seeds = [SEASON_SEED, LEADERBOARD_SEED], bump)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [SEASON_ARCHIVE_SEED, season.id.to_le_bytes().as_ref()],
+        bump,
+        space = SeasonArchive::SPACE)]
+    pub archive: Box<Account<'info, SeasonArchive>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player_name: String)]
+pub struct InitPlayer<'info> {
+    #[account(
+        init,
+        payer = player,
+        seeds = [PLAYER_SEED, player.key().as_ref()],
+        bump,
+        space = PlayerData::space(player_name.len()))]
+    pub player_data: Account<'info, PlayerData>,
+    #[account(
+        init,
+        payer = player,
+        seeds = [RUN_SEED, player.key().as_ref()],
+        bump,
+        space = RunData::SPACE)]
+    pub run: Account<'info, RunData>,
+    /// Already there if the player was closed before.
+    #[account(
+        init_if_needed,
+        payer = player,
+        seeds = [PLAYER_STATS_SEED, player.key().as_ref()],
+        bump,
+        space = PlayerStats::SPACE)]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+    /// Already there if someone holds the name, in any case.
+    #[account(
+        init_if_needed,
+        payer = player,
+        seeds = [NAME_SEED, name_seed(&player_name).as_ref()],
+        bump,
+        space = NameRegistry::SPACE)]
+    pub name_registry: Account<'info, NameRegistry>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(run_slot: u8)]
+pub struct OpenRunSlot<'info> {
+    #[account(
+        init,
+        payer = player,
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run_slot)],
+        bump,
+        space = RunData::SPACE)]
+    pub run: Account<'info, RunData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRunSlot<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.run_slot != 0 @ GameErrorCode::InvalidRunSlot,
+        constraint = run.status == RunStatus::Idle @ GameErrorCode::AlreadyInRun,
+        close = player)]
+    pub run: Account<'info, RunData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_name: String)]
+pub struct RenamePlayer<'info> {
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, player.key().as_ref()],
+        bump,
+        realloc = PlayerData::space(new_name.len()),
+        realloc::payer = player,
+        realloc::zero = false,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    /// CHECK: registry entry for the current name; closed in the handler if it exists and belongs
+    /// to this player
+    #[account(mut, seeds = [NAME_SEED, name_seed(&player_data.name).as_ref()], bump)]
+    pub old_name_registry: UncheckedAccount<'info>,
+
+    /// The same account as `old_name_registry` when only the case changes.
+    #[account(
+        init_if_needed,
+        payer = player,
+        seeds = [NAME_SEED, name_seed(&new_name).as_ref()],
+        bump,
+        space = NameRegistry::SPACE)]
+    pub new_name_registry: Account<'info, NameRegistry>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePlayer<'info> {
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, player.key().as_ref()],
+        bump,
+        constraint = !player_data.is_in_run @ GameErrorCode::AlreadyInRun,
+        close = player)]
+    pub player_data: Account<'info, PlayerData>,
+
+    /// Slot 0, created with the player; other slots are closed with `close_run_slot`.
+    #[account(
+        mut,
+        seeds = [RUN_SEED, player.key().as_ref()],
+        bump,
+        constraint = run.run_slot == 0 @ GameErrorCode::InvalidRunSlot,
+        close = player)]
+    pub run: Account<'info, RunData>,
+
+    /// Optional for players created before stats were tracked who never finished a run since.
+    /// Left open: it holds the run record count a re-created player continues from.
+    #[account(
+        seeds = [PLAYER_STATS_SEED, player.key().as_ref()],
+        bump,
+        constraint = player_stats.version == PlayerStats::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_stats: Option<Box<Account<'info, PlayerStats>>>,
+
+    /// CHECK: registry entry for the current name; closed in the handler if it exists and belongs
+    /// to this player
+    #[account(mut, seeds = [NAME_SEED, name_seed(&player_data.name).as_ref()], bump)]
+    pub name_registry: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    /// A leftover thread owned by the player's thread authority, deleted if passed.
+    #[account(mut, address = thread.pubkey(), constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, player.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(thread_id: Vec<u8>)]
+pub struct StartNewRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status == RunStatus::Idle @ GameErrorCode::AlreadyInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, player.key().as_ref()],
+        bump,
+        constraint = !player_data.is_run_active(run.run_slot) @ GameErrorCode::AlreadyInRun,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    #[account(address = system_program::ID)]
+    pub system_program: Program<'info, System>,
+
+    /// Omitted to start a run that is advanced by `tick` instead of a clockwork thread.
+    #[account(mut, address = Thread::pubkey(thread_authority.key(), thread_id))]
+    pub thread: Option<SystemAccount<'info>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, player.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+
+    #[account(
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        constraint = !program_admin.paused @ GameErrorCode::ProgramPaused)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    /// Set to play today's daily challenge.
+    #[account(
+        seeds = [DAILY_CHALLENGE_SEED, daily_challenge.day.to_le_bytes().as_ref()],
+        bump,
+        constraint = daily_challenge.day == utc_day(Clock::get()?.unix_timestamp) @ GameErrorCode::ChallengeNotToday)]
+    pub daily_challenge: Option<Account<'info, DailyChallenge>>,
+}
+
+#[derive(Accounts)]
+pub struct FinishRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Idle @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, player.key().as_ref()],
+        bump,
+        constraint = player_data.is_run_active(run.run_slot) @ GameErrorCode::NotInRun,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    /// Required when the run has a thread, omitted for cranked runs.
+    #[account(
+        mut,
+        address = thread.pubkey(),
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, player.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+
+    #[account(mut, seeds = [LEADERBOARD_SEED, run.difficulty.seed()], bump)]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+
+    #[account(seeds = [SEASON_SEED], bump)]
+    pub season: Account<'info, Season>,
+
+    #[account(mut, seeds = [SEASON_SEED, LEADERBOARD_SEED], bump)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+
+    /// Required when the run played a daily challenge.
+    #[account(
+        mut,
+        seeds = [DAILY_CHALLENGE_SEED, run.daily_day.to_le_bytes().as_ref(), LEADERBOARD_SEED],
+        bump)]
+    pub daily_leaderboard: Option<Box<Account<'info, Leaderboard>>>,
+
+    #[account(
+        init,
+        payer = player,
+        seeds = [RUN_RECORD_SEED, player.key().as_ref(), player_data.runs_finished.to_le_bytes().as_ref()],
+        bump,
+        space = RunRecord::SPACE)]
+    pub run_record: Box<Account<'info, RunRecord>>,
+
+    // init_if_needed covers players created before stats were tracked
+    #[account(
+        init_if_needed,
+        payer = player,
+        seeds = [PLAYER_STATS_SEED, player.key().as_ref()],
+        bump,
+        space = PlayerStats::SPACE)]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    #[account(seeds = [PROGRAM_ADMIN_SEED], bump)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Idle @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, run.authority.key().as_ref()],
+        bump,
+        constraint = player_data.is_run_active(run.run_slot) @ GameErrorCode::NotInRun,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    /// Receives the refund.
+    #[account(mut, address = run.authority)]
+    pub player: SystemAccount<'info>,
+
+    /// Anyone; pays for the run record and is paid the cleanup reward.
+    #[account(mut)]
+    pub cleaner: Signer<'info>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    /// Required when the run has a thread, omitted for cranked runs.
+    #[account(
+        mut,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+
+    #[account(mut, seeds = [LEADERBOARD_SEED, run.difficulty.seed()], bump)]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+
+    #[account(seeds = [SEASON_SEED], bump)]
+    pub season: Account<'info, Season>,
+
+    #[account(mut, seeds = [SEASON_SEED, LEADERBOARD_SEED], bump)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+
+    /// Required when the run played a daily challenge.
+    #[account(
+        mut,
+        seeds = [DAILY_CHALLENGE_SEED, run.daily_day.to_le_bytes().as_ref(), LEADERBOARD_SEED],
+        bump)]
+    pub daily_leaderboard: Option<Box<Account<'info, Leaderboard>>>,
+
+    #[account(
+        init,
+        payer = cleaner,
+        seeds = [RUN_RECORD_SEED, run.authority.key().as_ref(), player_data.runs_finished.to_le_bytes().as_ref()],
+        bump,
+        space = RunRecord::SPACE)]
+    pub run_record: Box<Account<'info, RunRecord>>,
+
+    #[account(
+        init_if_needed,
+        payer = cleaner,
+        seeds = [PLAYER_STATS_SEED, run.authority.key().as_ref()],
+        bump,
+        space = PlayerStats::SPACE)]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        constraint = !program_admin.paused @ GameErrorCode::ProgramPaused)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IncrementViaThread<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    /// Read for the run index when chaining the auto-finish.
+    #[account(
+        seeds = [PLAYER_SEED, run.authority.key().as_ref()],
+        bump,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    /// Verify that only the thread started with this run can execute the Increment Instruction
+    #[account(
+        signer,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Account<'info, Thread>,
+
+    /// The Thread Admin
+    /// The authority that was used as a seed to derive the thread address
+    /// `thread_authority` should equal `thread.thread_authority`
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+
+    #[account(seeds = [PROGRAM_ADMIN_SEED], bump)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+}
+
+#[derive(Accounts)]
+pub struct AutoFinishRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        mut,
+        seeds = [PLAYER_SEED, run.authority.key().as_ref()],
+        bump,
+        constraint = player_data.is_run_active(run.run_slot) @ GameErrorCode::NotInRun,
+        constraint = player_data.version == PlayerData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub player_data: Account<'info, PlayerData>,
+
+    #[account(mut, address = run.authority)]
+    pub player: SystemAccount<'info>,
+
+    /// Only the run's own thread can finish it on the player's behalf.
+    #[account(
+        signer,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Account<'info, Thread>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+
+    #[account(mut, seeds = [LEADERBOARD_SEED, run.difficulty.seed()], bump)]
+    pub leaderboard: Box<Account<'info, Leaderboard>>,
+
+    #[account(seeds = [SEASON_SEED], bump)]
+    pub season: Account<'info, Season>,
+
+    #[account(mut, seeds = [SEASON_SEED, LEADERBOARD_SEED], bump)]
+    pub season_leaderboard: Box<Account<'info, Leaderboard>>,
+
+    /// Required when the run played a daily challenge.
+    #[account(
+        mut,
+        seeds = [DAILY_CHALLENGE_SEED, run.daily_day.to_le_bytes().as_ref(), LEADERBOARD_SEED],
+        bump)]
+    pub daily_leaderboard: Option<Box<Account<'info, Leaderboard>>>,
+
+    /// CHECK: the record for the player's next finished run, created in the handler once its
+    /// address is checked; see `auto_finish_run`
+    #[account(mut)]
+    pub run_record: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [PLAYER_STATS_SEED, run.authority.key().as_ref()],
+        bump,
+        space = PlayerStats::SPACE)]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    /// The clockwork worker, reimbursed by the thread.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Tick<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Paused @ GameErrorCode::RunPaused,
+        constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated,
+        // a defeated run is only waiting for `finish_run`
+        constraint = run.heroes_alive() > 0 @ GameErrorCode::HeroesDefeated)]
+    pub run: Account<'info, RunData>,
+
+    /// Anyone can crank; the bounty, if any, goes to them.
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        constraint = !program_admin.paused @ GameErrorCode::ProgramPaused)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundCrankVault<'info> {
+    #[account(
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Idle @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    #[account(mut, seeds = [CRANK_VAULT_SEED, run.key().as_ref()], bump)]
+    pub crank_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpRunThread<'info> {
+    #[account(
+        seeds = [RUN_SEED, player.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Idle @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Whatever isn't spent on fees comes back to the player when the thread is deleted.
+    #[account(mut, constraint = thread.key() == run.thread @ GameErrorCode::WrongThread)]
+    pub thread: Account<'info, Thread>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts, Session)]
+#[instruction(card_id: u16, slot_id: u8)]
+pub struct Upgrade<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status != RunStatus::Paused @ GameErrorCode::RunPaused,
+        constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    #[account(
+        seeds = [PROGRAM_ADMIN_SEED],
+        bump,
+        constraint = !program_admin.paused @ GameErrorCode::ProgramPaused)]
+    pub program_admin: Account<'info, ProgramAdmin>,
+
+    pub user: Signer<'info>,
+
+    #[session(
+        // The ephemeral keypair signing the transaction
+        signer = user,
+        // The authority of the user account which must have created the session
+        authority = run.authority.key()
+    )]
+    // Session Tokens are passed as optional accounts
+    pub session_token: Option<Account<'info, SessionToken>>,
+}
+
+#[derive(Accounts, Session)]
+pub struct PauseRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status == RunStatus::Active @ GameErrorCode::NotInRun,
+        constraint = run.pause_used < MAX_PAUSE_SECONDS_PER_RUN @ GameErrorCode::PauseBudgetExhausted,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    pub user: Signer<'info>,
+
+    #[session(signer = user, authority = run.authority.key())]
+    pub session_token: Option<Account<'info, SessionToken>>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    /// Required when the run has a thread, omitted for cranked runs.
+    #[account(
+        mut,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+}
+
+#[derive(Accounts, Session)]
+pub struct ResumeRun<'info> {
+    #[account(
+        mut,
+        seeds = [RUN_SEED, run.authority.key().as_ref(), run_slot_seed(&run.run_slot)],
+        bump,
+        constraint = run.status == RunStatus::Paused @ GameErrorCode::RunNotPaused,
+        constraint = run.version == RunData::VERSION @ GameErrorCode::AccountNotMigrated)]
+    pub run: Account<'info, RunData>,
+
+    pub user: Signer<'info>,
+
+    #[session(signer = user, authority = run.authority.key())]
+    pub session_token: Option<Account<'info, SessionToken>>,
+
+    #[account(address = clockwork_sdk::ID)]
+    pub clockwork_program: Program<'info, clockwork_sdk::ThreadProgram>,
+
+    /// Required when the run has a thread, omitted for cranked runs.
+    #[account(
+        mut,
+        constraint = thread.key() == run.thread @ GameErrorCode::WrongThread,
+        constraint = thread.authority.eq(&thread_authority.key()))]
+    pub thread: Option<Account<'info, Thread>>,
+
+    #[account(seeds = [THREAD_AUTHORITY_SEED, run.authority.key().as_ref()], bump)]
+    pub thread_authority: SystemAccount<'info>,
+}
+
+/// Program-wide settings owned by the admin, a single PDA.
+#[account]
+pub struct ProgramAdmin {
+    //32
+    pub admin: Pubkey,
+    //32, default when no handover is in progress
+    pub pending_admin: Pubkey,
+    //1, stops new runs, card plays and ticks everywhere
+    pub paused: bool,
+    //1
+    pub version: u8,
+    //8, start of the current or last pause
+    pub paused_at: i64,
+    //8, end of the last pause
+    pub unpaused_at: i64,
+}
+
+impl ProgramAdmin {
+    pub const VERSION: u8 = 2;
+    pub const SPACE: usize = 8 + 32 + 32 + 1 + 1 + 8 + 8;
+
+    /// Seconds of the current or last pause that fall between `from` and `to`.
+    pub fn paused_between(&self, from: i64, to: i64) -> i64 {
+        let pause_end = if self.paused { to } else { self.unpaused_at };
+        to.min(pause_end)
+            .saturating_sub(from.max(self.paused_at))
+            .max(0)
+    }
+}
+
+#[event]
+pub struct ThreadBalanceLow {
+    pub run: Pubkey,
+    pub thread: Pubkey,
+    /// lamports above the thread's rent-exempt minimum
+    pub balance: u64,
+    pub executions_left: u64,
+}
+
+#[account]
+pub struct RunData {
+    //32
+    pub authority: Pubkey,
     //8
     pub score: u64,
     //2
     pub experience: u16,
-    //(1 + 10) * 7 = 77
+    //(1 + 15) * 7 = 112
     pub slots: [Option<CharacterInfo>; 7],
     //2
     pub last_character_id: u16,
@@ -826,42 +2566,1415 @@ pub struct RunData {
     pub cards: [CardInfo; 3],
     //2
     pub last_card_id: u16,
+    //8
+    pub seed: u64,
+    //8
+    pub rng_state: u64,
+    //8
+    pub started_at: i64,
+    //4
+    pub ticks: u32,
+    //2
+    pub wave: u16,
+    //4
+    pub kills: u32,
+    //2 * 7 = 14, by character type
+    pub kills_by_type: [u16; CHARACTER_TYPE_COUNT],
+    //4
+    pub damage_dealt: u32,
+    //4
+    pub damage_taken: u32,
+    //2 * 3 = 6
+    pub cards_played_by_type: [u16; CARD_TYPE_COUNT],
+    //1 * 3 = 3
+    pub heroes_lost_by_type: [u8; HERO_TYPE_COUNT],
+    //1
+    pub status: RunStatus,
+    //32, the clockwork thread driving the active run, default for cranked runs
+    pub thread: Pubkey,
+    //8
+    pub last_tick_at: i64,
+    //1, seconds between executions of the run's thread
+    pub thread_interval: u8,
+    //8, when the current pause started, 0 unless paused
+    pub paused_at: i64,
+    //8, seconds of game time frozen so far this run
+    pub pause_used: i64,
+    //8, anyone may expire the run after this; pushed back by the player's actions
+    pub expires_at: i64,
+    //32, where `score` came from; `score` is its total scaled by the difficulty
+    pub score_breakdown: ScoreBreakdown,
+    //1, layout version; `migrate_run` brings older accounts up to `RunData::VERSION`
+    pub version: u8,
+    //1, which of the player's runs this is, part of the seeds
+    pub run_slot: u8,
+    //8, enemy spawns draw from their own stream, so card plays can't change what comes next
+    pub spawn_rng_state: u64,
+    //4, UTC day of the daily challenge this run plays, 0 for ordinary runs
+    pub daily_day: u32,
+    //4
+    pub modifiers: ChallengeModifiers,
+    //1, fixed at start; picks the enemy tables, tick rate, score multiplier and leaderboard
+    pub difficulty: Difficulty,
+    //2, game time past `last_tick_at` already counted towards the next tick
+    pub tick_carry_ms: u16,
+    //1, `MUTATOR_*` bits, fixed at start
+    pub mutators: u8,
+    //8, `ProgramAdmin.paused_at` of the last global pause already skipped
+    pub skipped_pause_at: i64,
+}
+
+impl RunData {
+    pub const SPACE: usize = 8
+        + 32 + 8 + 2 + 7 * (1 + CharacterInfo::SPACE) + 2 + 9 + 2
+        + 8 + 8 + 8 + 4 + 2 + 4
+        + 14 + 4 + 4 + 6 + 3
+        + 1
+        + 32 + 8 + 1
+        + 8 + 8 + 8
+        + 32
+        + 1 + 1
+        + 8 + 4 + 4
+        + 1 + 2
+        + 1
+        + 8;
+    pub const VERSION: u8 = 8;
+    /// Size of accounts created before versioning, see `LegacyRunData`.
+    pub const LEGACY_SPACE: usize = 8 + 32 + 8 + 2 + 77 + 2 + 9 + 2;
+    /// Size of version 5 accounts, the last whose slots held `CharacterInfoV5`.
+    pub const V5_SPACE: usize =
+        RunData::V6_SPACE - 7 * (CharacterInfoV6::SPACE - CharacterInfoV5::SPACE);
+    /// Size of version 6 accounts, whose slots held `CharacterInfoV6`; version 8 appended
+    /// `skipped_pause_at`.
+    pub const V6_SPACE: usize =
+        RunData::SPACE - 8 - 7 * (CharacterInfo::SPACE - CharacterInfoV6::SPACE);
+    /// Where `slots` starts: discriminator, authority, score and experience.
+    pub const SLOTS_OFFSET: usize = 8 + 32 + 8 + 2;
+
+    /// Advances the run's RNG, so every draw is reproducible from `seed`.
+    pub fn next_random(&mut self) -> u64 {
+        self.rng_state = xorshift64(self.rng_state);
+        self.rng_state
+    }
+
+    pub fn next_spawn_random(&mut self) -> u64 {
+        self.spawn_rng_state = xorshift64(self.spawn_rng_state);
+        self.spawn_rng_state
+    }
+
+    pub fn new_enemy(&self, id: u16, character_type: u8) -> CharacterInfo {
+        let difficulty = self.difficulty as usize;
+        let mut health_percent = ENEMY_HEALTH_PERCENT_BY_DIFFICULTY[difficulty] as u32
+            * curve_percent(&ENEMY_HEALTH_CURVE, self.wave)
+            / 100;
+        if self.has_mutator(MUTATOR_TOUGH_ENEMIES) {
+            health_percent = health_percent * TOUGH_ENEMIES_HEALTH_PERCENT as u32 / 100;
+        }
+        let attack_percent = ENEMY_ATTACK_PERCENT_BY_DIFFICULTY[difficulty] as u32
+            * curve_percent(&ENEMY_ATTACK_CURVE, self.wave)
+            / 100;
+        let mut enemy = CharacterInfo::new(id, 1, character_type);
+        enemy.max_health = scale_stat(enemy.max_health, health_percent)
+            .saturating_add(self.modifiers.enemy_health_bonus as u16);
+        enemy.health = enemy.max_health;
+        enemy.attack_damage = scale_stat(enemy.attack_damage, attack_percent)
+            .saturating_add(self.modifiers.enemy_attack_bonus as u16);
+        enemy
+    }
+
+    /// Picks the type of the next enemy from the difficulty's spawn weights.
+    pub fn next_enemy_type(&mut self) -> u8 {
+        let weights = SPAWN_WEIGHTS_BY_DIFFICULTY[self.difficulty as usize];
+        let total: u64 = weights.iter().map(|weight| *weight as u64).sum();
+        let mut roll = self.next_spawn_random() % total.max(1);
+        for (offset, weight) in weights.iter().enumerate() {
+            if roll < *weight as u64 {
+                return (HERO_TYPE_COUNT + offset) as u8;
+            }
+            roll -= *weight as u64;
+        }
+        (CHARACTER_TYPE_COUNT - 1) as u8
+    }
+
+    /// Nothing for a run that hasn't cleared a wave, so starting and finishing at once is worthless.
+    pub fn extraction_bonus(&self) -> u64 {
+        let waves_cleared = (self.wave.saturating_sub(1) as u64).min(EXTRACTION_FULL_BONUS_WAVES);
+        EXTRACTION_BONUS_SCORE * waves_cleared / EXTRACTION_FULL_BONUS_WAVES
+    }
+
+    /// `score_breakdown` scaled by the difficulty's score multiplier and each mutator's bonus.
+    pub fn scaled_score(&self) -> u64 {
+        let mutator_percent = 100
+            + (0..MUTATOR_COUNT)
+                .filter(|bit| self.has_mutator(1 << bit))
+                .map(|bit| MUTATOR_SCORE_BONUS_PERCENT[bit])
+                .sum::<u64>();
+        self.score_breakdown
+            .total()
+            .saturating_mul(SCORE_PERCENT_BY_DIFFICULTY[self.difficulty as usize])
+            / 100
+            * mutator_percent
+            / 100
+    }
+
+    pub fn has_mutator(&self, mutator: u8) -> bool {
+        self.mutators & mutator != 0
+    }
+
+    /// Takes a card's cost out of `experience`; experience comes from kills, so paying for cards
+    /// keeps upgrades earned.
+    pub fn pay_for_card(&mut self, card_type: u8) -> Result<()> {
+        let mut card_cost = *CARD_COST_BY_TYPE
+            .get(card_type as usize)
+            .ok_or(GameErrorCode::InvalidCardType)?;
+        if self.has_mutator(MUTATOR_EXPENSIVE_CARDS) {
+            card_cost = card_cost.saturating_mul(EXPENSIVE_CARDS_COST_MULTIPLIER);
+        }
+        self.experience = self
+            .experience
+            .checked_sub(card_cost as u16)
+            .ok_or(GameErrorCode::InsufficientExperience)?;
+        Ok(())
+    }
+
+    pub fn record_hit_on_enemy(&mut self, enemy: &CharacterInfo, attack_damage: u16) {
+        self.damage_dealt = self
+            .damage_dealt
+            .saturating_add(attack_damage.min(enemy.health) as u32);
+        if attack_damage >= enemy.health {
+            self.kills = self.kills.saturating_add(1);
+            if let Some(kills) = self.kills_by_type.get_mut(enemy.character_type as usize) {
+                *kills = kills.saturating_add(1);
+            }
+            let kill_score = KILL_SCORE_BY_TYPE
+                .get(enemy.character_type as usize)
+                .copied()
+                .unwrap_or(0);
+            self.score_breakdown.kills = self.score_breakdown.kills.saturating_add(kill_score);
+        }
+    }
+
+    pub fn record_hit_on_hero(&mut self, hero: &CharacterInfo, attack_damage: u16) {
+        self.damage_taken = self
+            .damage_taken
+            .saturating_add(attack_damage.min(hero.health) as u32);
+        if attack_damage >= hero.health {
+            if let Some(lost) = self.heroes_lost_by_type.get_mut(hero.character_type as usize) {
+                *lost = lost.saturating_add(1);
+            }
+        }
+    }
+
+    /// Whole tick intervals elapsed since the last processed tick, which ended `tick_carry_ms`
+    /// past `last_tick_at`.
+    pub fn ticks_due(&self, now: i64) -> u32 {
+        let elapsed_ms = now
+            .saturating_sub(self.last_tick_at)
+            .max(0)
+            .saturating_mul(1000)
+            .saturating_sub(self.tick_carry_ms as i64)
+            .max(0);
+        (elapsed_ms / self.tick_interval_ms() as i64).min(u32::MAX as i64) as u32
+    }
+
+    /// Moves game time forward by `ticks` intervals. Ticks need not end on a whole second, so the
+    /// remainder is carried in `tick_carry_ms`.
+    pub fn advance_ticks(&mut self, ticks: u32) {
+        let advanced_ms = self.tick_carry_ms as i64 + ticks as i64 * self.tick_interval_ms() as i64;
+        self.last_tick_at = self.last_tick_at.saturating_add(advanced_ms / 1000);
+        self.tick_carry_ms = (advanced_ms % 1000) as u16;
+    }
+
+    pub fn tick_interval_ms(&self) -> u32 {
+        TICK_INTERVAL_MS_BY_DIFFICULTY[self.difficulty as usize]
+    }
+
+    /// Skips the part of the last global pause this run hasn't been ticked through, once, so a
+    /// cranked run is frozen through it the same as a threaded one.
+    pub fn skip_global_pause(&mut self, program_admin: &ProgramAdmin, now: i64) {
+        if program_admin.paused || self.skipped_pause_at == program_admin.paused_at {
+            return;
+        }
+        let paused = program_admin.paused_between(self.last_tick_at, now);
+        self.last_tick_at = self.last_tick_at.saturating_add(paused);
+        self.skipped_pause_at = program_admin.paused_at;
+    }
+
+    /// The clock as the game sees it: a paused run stands still for as long as its pause budget
+    /// lasts, see `resume_run`.
+    pub fn game_now(&self, now: i64) -> i64 {
+        if self.status != RunStatus::Paused {
+            return now;
+        }
+        let budget_left = MAX_PAUSE_SECONDS_PER_RUN.saturating_sub(self.pause_used).max(0);
+        now.saturating_sub(now.saturating_sub(self.paused_at).min(budget_left))
+    }
+
+    pub fn refresh_deadline(&mut self, now: i64) {
+        self.expires_at = now.saturating_add(RUN_INACTIVITY_TIMEOUT_SECONDS);
+    }
+
+    pub fn natural_end_reason(&self) -> RunEndReason {
+        if self.heroes_alive() == 0 {
+            RunEndReason::Defeated
+        } else {
+            RunEndReason::Extracted
+        }
+    }
+
+    pub fn thread_interval_seconds(&self) -> u8 {
+        if self.wave <= EARLY_WAVES {
+            EARLY_WAVE_THREAD_INTERVAL_SECONDS
+        } else {
+            THREAD_INTERVAL_SECONDS
+        }
+    }
+
+    pub fn heroes_alive(&self) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|character_info| character_info.alignment == 0)
+            .count()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum RunStatus {
+    Idle,
+    Active,
+    Paused,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Extra leaderboard seed; Normal's is empty so it keeps the original board.
+    pub fn seed(&self) -> &'static [u8] {
+        match self {
+            Difficulty::Easy => b"easy",
+            Difficulty::Normal => b"",
+            Difficulty::Hard => b"hard",
+        }
+    }
+}
+
+/// `RunData` as it was stored before accounts were versioned; its serialization is a prefix of
+/// the version 5 one.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyRunData {
+    pub authority: Pubkey,
+    pub score: u64,
+    pub experience: u16,
+    pub slots: [Option<CharacterInfoV5>; 7],
+    pub last_character_id: u16,
+    pub cards: [CardInfo; 3],
+    pub last_card_id: u16,
+}
+
+#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+// size: 8 + 8 + 8 + 8 = 32
+pub struct ScoreBreakdown {
+    pub kills: u64,
+    pub waves: u64,
+    pub survival: u64,
+    pub extraction: u64,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> u64 {
+        self.kills
+            .saturating_add(self.waves)
+            .saturating_add(self.survival)
+            .saturating_add(self.extraction)
+    }
+}
+
+#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+// size: 2 + 1 + 1 + 2 + 2 + 2 + 2 + 2 + 1 = 15
+pub struct CharacterInfo {
+    pub id: u16,
+    pub alignment: u8,
+    pub character_type: u8,
+    pub cooldown: u16,
+    pub cooldown_timer: u16,
+    pub max_health: u16,
+    pub health: u16,
+    pub attack_damage: u16,
+    pub state: u8
+}
+
+/// `CharacterInfo` as stored up to `RunData` version 5, before enemy scaling needed wider stats.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone)]
+// size: 2 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 = 10
+pub struct CharacterInfoV5 {
+    pub id: u16,
+    pub alignment: u8,
+    pub character_type: u8,
+    pub cooldown: u8,
+    pub cooldown_timer: u8,
+    pub max_health: u8,
+    pub health: u8,
+    pub attack_damage: u8,
+    pub state: u8,
+}
+
+impl CharacterInfoV5 {
+    pub const SPACE: usize = 2 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1;
+}
+
+impl From<CharacterInfoV5> for CharacterInfo {
+    fn from(stored: CharacterInfoV5) -> Self {
+        CharacterInfo {
+            id: stored.id,
+            alignment: stored.alignment,
+            character_type: stored.character_type,
+            cooldown: stored.cooldown as u16,
+            cooldown_timer: stored.cooldown_timer as u16,
+            max_health: stored.max_health as u16,
+            health: stored.health as u16,
+            attack_damage: stored.attack_damage as u16,
+            state: stored.state,
+        }
+    }
+}
+
+/// `CharacterInfo` as stored in `RunData` version 6, with cooldowns still u8.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone)]
+// size: 2 + 1 + 1 + 1 + 1 + 2 + 2 + 2 + 1 = 13
+pub struct CharacterInfoV6 {
+    pub id: u16,
+    pub alignment: u8,
+    pub character_type: u8,
+    pub cooldown: u8,
+    pub cooldown_timer: u8,
+    pub max_health: u16,
+    pub health: u16,
+    pub attack_damage: u16,
+    pub state: u8,
+}
+
+impl CharacterInfoV6 {
+    pub const SPACE: usize = 2 + 1 + 1 + 1 + 1 + 2 + 2 + 2 + 1;
+}
+
+impl From<CharacterInfoV6> for CharacterInfo {
+    fn from(stored: CharacterInfoV6) -> Self {
+        CharacterInfo {
+            id: stored.id,
+            alignment: stored.alignment,
+            character_type: stored.character_type,
+            cooldown: stored.cooldown as u16,
+            cooldown_timer: stored.cooldown_timer as u16,
+            max_health: stored.max_health,
+            health: stored.health,
+            attack_damage: stored.attack_damage,
+            state: stored.state,
+        }
+    }
+}
+
+impl CharacterInfo {
+    pub const SPACE: usize = 2 + 1 + 1 + 2 + 2 + 2 + 2 + 2 + 1;
+
+    /// A fresh character with the base stats of its type.
+    pub fn new(id: u16, alignment: u8, character_type: u8) -> Self {
+        let stats_index = (character_type as usize).min(CHARACTER_TYPE_COUNT - 1);
+        CharacterInfo {
+            id,
+            alignment,
+            character_type,
+            cooldown: COOLDOWN_BY_TYPE[stats_index],
+            cooldown_timer: COOLDOWN_BY_TYPE[stats_index],
+            max_health: HEALTH_BY_TYPE[stats_index],
+            health: HEALTH_BY_TYPE[stats_index],
+            attack_damage: ATTACK_BY_TYPE[stats_index],
+            state: 0,
+        }
+    }
+
+    pub fn update_timer(&mut self, new_timer: u16) {
+        self.cooldown_timer = new_timer;
+    }
+}
+
+#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+//size: 2 + 1
+pub struct CardInfo {
+    pub id: u16,
+    pub card_type: u8,
+}
+
+#[account]
+pub struct PlayerData {
+    pub authority: Pubkey,
+    pub name: String,
+    pub runs_finished: u32,
+    pub best_score: u64,
+    // whether any of the player's runs is active
+    pub is_in_run: bool,
+    // layout version; `migrate_player` brings older accounts up to `PlayerData::VERSION`
+    pub version: u8,
+    // bit n set while run slot n is active
+    pub active_runs: u8,
+    // UTC day of the last daily challenge started, each can be played once
+    pub last_daily_day: u32,
+}
+
+impl PlayerData {
+    pub const VERSION: u8 = 3;
+
+    pub fn space(name_length: usize) -> usize {
+        8 + 32 + 4 + name_length + 4 + 8 + 1 + 1 + 1 + 4
+    }
+
+    pub fn is_run_active(&self, run_slot: u8) -> bool {
+        self.active_runs & run_slot_bit(run_slot) != 0
+    }
+
+    pub fn set_run_active(&mut self, run_slot: u8, active: bool) {
+        if active {
+            self.active_runs |= run_slot_bit(run_slot);
+        } else {
+            self.active_runs &= !run_slot_bit(run_slot);
+        }
+        self.is_in_run = self.active_runs != 0;
+    }
+}
+
+/// Claims a name for one player. Seeded by the hash of the normalized name, so names are unique
+/// regardless of case.
+#[account]
+pub struct NameRegistry {
+    //32
+    pub player: Pubkey,
+    //4 + 16
+    pub name: String,
+    //1
+    pub version: u8,
+}
+
+impl NameRegistry {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + 32 + 4 + MAX_NAME_LENGTH + 1;
+}
+
+pub fn validate_name(name: &str) -> Result<()> {
+    require!(name.len() >= MIN_NAME_LENGTH, GameErrorCode::NameTooShort);
+    require!(name.len() <= MAX_NAME_LENGTH, GameErrorCode::NameTooLong);
+    require!(
+        name.bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-'),
+        GameErrorCode::InvalidNameCharacter
+    );
+    Ok(())
+}
+
+/// Seed of the registry entry for a name: the hash of its lowercase form. Hashing keeps the seed
+/// within the 32-byte limit for any stored name, including ones that predate validation.
+pub fn name_seed(name: &str) -> [u8; 32] {
+    hash(name.to_ascii_lowercase().as_bytes()).to_bytes()
+}
+
+/// Closes the registry entry at `registry` if it exists and belongs to `player`.
+pub fn release_name<'info>(registry: &AccountInfo<'info>, player: &AccountInfo<'info>) -> Result<()> {
+    if registry.owner != &ID || registry.data_is_empty() {
+        return Ok(());
+    }
+    let name_registry = Account::<NameRegistry>::try_from(registry)?;
+    if name_registry.player != player.key() {
+        return Ok(());
+    }
+    name_registry.close(player.clone())
+}
+
+/// Lifetime totals, rolled up from the run counters by `finish_run`.
+#[account]
+pub struct PlayerStats {
+    //32
+    pub authority: Pubkey,
+    //4 * 7 = 28, by character type
+    pub kills_by_type: [u32; CHARACTER_TYPE_COUNT],
+    //8
+    pub damage_dealt: u64,
+    //8
+    pub damage_taken: u64,
+    //4 * 3 = 12
+    pub cards_played_by_type: [u32; CARD_TYPE_COUNT],
+    //4 * 3 = 12
+    pub heroes_lost_by_type: [u32; HERO_TYPE_COUNT],
+    //8
+    pub ticks_survived: u64,
+    //4
+    pub extractions: u32,
+    //4
+    pub deaths: u32,
+    //1
+    pub version: u8,
+    //4, run records written so far; kept when the player is closed so a re-created player
+    //doesn't reuse their seeds
+    pub runs_recorded: u32,
+}
+
+impl PlayerStats {
+    pub const VERSION: u8 = 2;
+    pub const SPACE: usize = 8 + 32 + 28 + 8 + 8 + 12 + 12 + 8 + 4 + 4 + 1 + 4;
+
+    pub fn add_run(&mut self, run: &RunData, end_reason: RunEndReason) {
+        for (total, kills) in self.kills_by_type.iter_mut().zip(run.kills_by_type) {
+            *total = total.saturating_add(kills as u32);
+        }
+        self.damage_dealt = self.damage_dealt.saturating_add(run.damage_dealt as u64);
+        self.damage_taken = self.damage_taken.saturating_add(run.damage_taken as u64);
+        for (total, played) in self.cards_played_by_type.iter_mut().zip(run.cards_played_by_type) {
+            *total = total.saturating_add(played as u32);
+        }
+        for (total, lost) in self.heroes_lost_by_type.iter_mut().zip(run.heroes_lost_by_type) {
+            *total = total.saturating_add(lost as u32);
+        }
+        self.ticks_survived = self.ticks_survived.saturating_add(run.ticks as u64);
+        match end_reason {
+            RunEndReason::Extracted => self.extractions = self.extractions.saturating_add(1),
+            RunEndReason::Defeated => self.deaths = self.deaths.saturating_add(1),
+            RunEndReason::Abandoned | RunEndReason::ForceFinished => {}
+        }
+    }
+}
+
+/// Immutable summary of one finished run, seeded by player and the run's index.
+#[account]
+pub struct RunRecord {
+    //32
+    pub player: Pubkey,
+    //4
+    pub run_index: u32,
+    //8
+    pub score: u64,
+    //2
+    pub waves: u16,
+    //4
+    pub kills: u32,
+    //4
+    pub ticks: u32,
+    //8
+    pub started_at: i64,
+    //8, seconds
+    pub duration: i64,
+    //8
+    pub seed: u64,
+    //1
+    pub end_reason: RunEndReason,
+    //1
+    pub version: u8,
+    //1, what the score was earned against
+    pub difficulty: Difficulty,
+    //1
+    pub mutators: u8,
+}
+
+impl RunRecord {
+    pub const VERSION: u8 = 2;
+    pub const SPACE: usize = 8 + 32 + 4 + 8 + 2 + 4 + 4 + 8 + 8 + 8 + 1 + 1 + 1 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+pub enum RunEndReason {
+    Extracted,
+    Defeated,
+    Abandoned,
+    ForceFinished,
+}
+
+#[account]
+pub struct Leaderboard {
+    //4 + 68 * LEADERBOARD_SIZE, sorted best first
+    pub entries: Vec<LeaderboardEntry>,
+    //1
+    pub version: u8,
+}
+
+impl Leaderboard {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + 4 + LeaderboardEntry::SPACE * LEADERBOARD_SIZE + 1;
+
+    /// Inserts the entry if it makes the top `LEADERBOARD_SIZE`, keeping at most one entry per
+    /// player. Returns true if the board changed.
+    pub fn submit(&mut self, entry: LeaderboardEntry) -> bool {
+        if let Some(existing) = self.entries.iter().position(|e| e.player == entry.player) {
+            if !entry.ranks_above(&self.entries[existing]) {
+                return false;
+            }
+            self.entries.remove(existing);
+        }
+
+        let position = self
+            .entries
+            .iter()
+            .position(|e| entry.ranks_above(e))
+            .unwrap_or(self.entries.len());
+        if position >= LEADERBOARD_SIZE {
+            return false;
+        }
+
+        self.entries.insert(position, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        true
+    }
+}
+
+#[derive(Default, AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+//size: 32 + (4 + 16) + 8 + 8 = 68
+pub struct LeaderboardEntry {
+    pub player: Pubkey,
+    pub name: String,
+    pub score: u64,
+    pub timestamp: i64,
+}
+
+impl LeaderboardEntry {
+    pub const SPACE: usize = 32 + 4 + LEADERBOARD_NAME_LENGTH + 8 + 8;
+
+    /// Higher score wins, then the earlier timestamp, then the lower player key, so the order
+    /// never depends on submission order.
+    pub fn ranks_above(&self, other: &LeaderboardEntry) -> bool {
+        match self.score.cmp(&other.score) {
+            std::cmp::Ordering::Equal => {
+                (self.timestamp, self.player) < (other.timestamp, other.player)
+            }
+            ordering => ordering == std::cmp::Ordering::Greater,
+        }
+    }
+}
+
+#[account]
+pub struct Season {
+    //4
+    pub id: u32,
+    //8
+    pub start_timestamp: i64,
+    //8
+    pub end_timestamp: i64,
+    //8, length of every season in seconds
+    pub duration: i64,
+    //1
+    pub version: u8,
+}
+
+impl Season {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + 4 + 8 + 8 + 8 + 1;
+
+    pub fn is_active(&self, now: i64) -> bool {
+        now >= self.start_timestamp && now < self.end_timestamp
+    }
+}
+
+/// The same run for everyone on one UTC day: seed, hero lineup and modifiers.
+#[account]
+pub struct DailyChallenge {
+    //4, days since the unix epoch
+    pub day: u32,
+    //8
+    pub seed: u64,
+    //3, hero types for slots 0..3
+    pub lineup: [u8; HERO_TYPE_COUNT],
+    //4
+    pub modifiers: ChallengeModifiers,
+    //8
+    pub created_at: i64,
+    //1
+    pub version: u8,
+}
+
+impl DailyChallenge {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + 4 + 8 + 3 + 4 + 8 + 1;
+}
+
+#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
+// size: 1 + 1 + 2 = 4
+pub struct ChallengeModifiers {
+    pub enemy_health_bonus: u8,
+    pub enemy_attack_bonus: u8,
+    pub starting_experience: u16,
+}
+
+/// `value` at `percent` of itself, never below 1 so scaled-down enemies still have a stat.
+pub fn scale_stat(value: u16, percent: u32) -> u16 {
+    (value as u64 * percent as u64 / 100).clamp(1, u16::MAX as u64) as u16
 }
 
-#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
-// size: 2 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 = 10
-pub struct CharacterInfo {
-    pub id: u16,
-    pub alignment: u8,
-    pub character_type: u8,
-    pub cooldown: u8,
-    pub cooldown_timer: u8,
-    pub max_health: u8,
-    pub health: u8,
-    pub attack_damage: u8,
-    pub state: u8
+/// Reads `curve` at `wave`, see `ENEMY_HEALTH_CURVE`.
+pub fn curve_percent(curve: &[(u16, u16)], wave: u16) -> u32 {
+    match curve.first() {
+        None => return 100,
+        Some(&(first_wave, first_percent)) if wave <= first_wave => return first_percent as u32,
+        _ => {}
+    }
+    for points in curve.windows(2) {
+        let (from_wave, from_percent) = points[0];
+        let (to_wave, to_percent) = points[1];
+        if wave <= to_wave {
+            let progress = (wave - from_wave) as i64;
+            let span = (to_wave - from_wave).max(1) as i64;
+            let rise = to_percent as i64 - from_percent as i64;
+            return (from_percent as i64 + rise * progress / span) as u32;
+        }
+    }
+    curve.last().map_or(100, |&(_, percent)| percent as u32)
 }
 
-impl CharacterInfo {
-    pub fn update_timer(&mut self, new_timer: u8) {
-        self.cooldown_timer = new_timer;
+/// Brings a run account, already grown to `RunData::SPACE`, from the layout it was stored with
+/// (`stored_len` bytes) up to `RunData::VERSION`.
+pub fn upgrade_run_data(data: &mut [u8], stored_len: usize, run_slot: u8) -> Result<()> {
+    if stored_len == RunData::LEGACY_SPACE {
+        // Slots serialize to a variable length, so the unversioned account can hold stale
+        // bytes past its last field; only the fields themselves are carried over.
+        require!(
+            data[..8] == RunData::discriminator(),
+            GameErrorCode::UnknownAccountLayout
+        );
+        let legacy = LegacyRunData::deserialize(&mut &data[8..stored_len])?;
+        data[8..].fill(0);
+        legacy.serialize(&mut &mut data[8..])?;
+    }
+
+    if stored_len <= RunData::V5_SPACE {
+        widen_run_slots::<CharacterInfoV5>(data)?;
+    } else if stored_len <= RunData::V6_SPACE {
+        widen_run_slots::<CharacterInfoV6>(data)?;
+    }
+
+    // Fields appended in a later version may sit on stale bytes for the same reason, so each
+    // is set explicitly when upgrading past the version that added it.
+    let mut run_data = RunData::try_deserialize(&mut &data[..])?;
+    if run_data.version < RunData::VERSION {
+        if run_data.version == 0 {
+            run_data.status = RunStatus::Idle;
+        }
+        if run_data.version < 2 {
+            run_data.run_slot = run_slot;
+        }
+        if run_data.version < 3 {
+            run_data.spawn_rng_state = run_data.rng_state.max(1);
+            run_data.daily_day = 0;
+            run_data.modifiers = ChallengeModifiers::default();
+        }
+        if run_data.version < 4 {
+            run_data.difficulty = Difficulty::Normal;
+            run_data.tick_carry_ms = 0;
+        }
+        if run_data.version < 5 {
+            run_data.mutators = 0;
+        }
+        if run_data.version < 8 {
+            run_data.skipped_pause_at = 0;
+        }
+        run_data.version = RunData::VERSION;
+        run_data.try_serialize(&mut &mut data[..])?;
     }
+    Ok(())
 }
 
-#[derive(Default, AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq, Eq)]
-//size: 2 + 1
-pub struct CardInfo {
-    pub id: u16,
-    pub card_type: u8,
+/// Rewrites the slots of a run stored with an older `CharacterInfo` layout `T`. Everything after
+/// the slots moves back by however much they grew and is otherwise copied as-is; the account must
+/// already be grown to `RunData::SPACE`.
+pub fn widen_run_slots<T>(data: &mut [u8]) -> Result<()>
+where
+    T: AnchorDeserialize + Into<CharacterInfo> + Copy,
+{
+    let (slots, rest) = {
+        let mut cursor = &data[RunData::SLOTS_OFFSET..];
+        let slots = <[Option<T>; 7]>::deserialize(&mut cursor)?;
+        (slots, cursor.to_vec())
+    };
+    let slots: [Option<CharacterInfo>; 7] = slots.map(|slot| slot.map(Into::into));
+
+    data[RunData::SLOTS_OFFSET..].fill(0);
+    let mut cursor = &mut data[RunData::SLOTS_OFFSET..];
+    slots.serialize(&mut cursor)?;
+    // only the stale bytes past the last field can fall off the end
+    let room = cursor.len().min(rest.len());
+    cursor[..room].copy_from_slice(&rest[..room]);
+    Ok(())
+}
+
+pub fn utc_day(now: i64) -> u32 {
+    (now.max(0) / SECONDS_PER_DAY).min(u32::MAX as i64) as u32
 }
 
+/// Final standings of a finished season. Written once by `rollover_season` and never modified.
 #[account]
-pub struct PlayerData {
-    pub authority: Pubkey,
-    pub name: String,
-    pub runs_finished: u32,
-    pub best_score: u64,
-    pub is_in_run: bool,
+pub struct SeasonArchive {
+    pub season_id: u32,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub entries: Vec<LeaderboardEntry>,
+    pub version: u8,
+}
+
+impl SeasonArchive {
+    pub const VERSION: u8 = 1;
+    pub const SPACE: usize = 8 + 4 + 8 + 8 + 4 + LeaderboardEntry::SPACE * LEADERBOARD_SIZE + 1;
+}
+
+/// Cuts the name down to `LEADERBOARD_NAME_LENGTH` bytes without splitting a character.
+pub fn leaderboard_name(name: &str) -> String {
+    let mut end = 0;
+    for (index, character) in name.char_indices() {
+        if index + character.len_utf8() > LEADERBOARD_NAME_LENGTH {
+            break;
+        }
+        end = index + character.len_utf8();
+    }
+    name[..end].to_string()
+}
+
+/// Book-keeping shared by every way a run can end: run record, lifetime stats, best score and
+/// leaderboards. Leaves the run idle; deleting the thread and refunding is up to the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn finalize_run(
+    run: &mut RunData,
+    player_data: &mut PlayerData,
+    player_stats: &mut PlayerStats,
+    run_record: &mut RunRecord,
+    leaderboard: &mut Leaderboard,
+    season: &Season,
+    season_leaderboard: &mut Leaderboard,
+    daily_leaderboard: Option<&mut Leaderboard>,
+    end_reason: RunEndReason,
+    now: i64,
+) -> Result<()> {
+    if end_reason == RunEndReason::Extracted {
+        run.score_breakdown.extraction = run.extraction_bonus();
+    }
+    run.score = run.scaled_score();
+
+    run_record.player = run.authority;
+    run_record.run_index = player_data.runs_finished;
+    run_record.score = run.score;
+    run_record.waves = run.wave;
+    run_record.kills = run.kills;
+    run_record.ticks = run.ticks;
+    run_record.started_at = run.started_at;
+    run_record.duration = now.saturating_sub(run.started_at);
+    run_record.seed = run.seed;
+    run_record.end_reason = end_reason;
+    run_record.difficulty = run.difficulty;
+    run_record.mutators = run.mutators;
+    run_record.version = RunRecord::VERSION;
+
+    player_stats.authority = run.authority;
+    player_stats.version = PlayerStats::VERSION;
+    player_stats.add_run(run, run_record.end_reason);
+
+    player_data.runs_finished = player_data
+        .runs_finished
+        .checked_add(1)
+        .ok_or(GameErrorCode::CounterOverflow)?;
+    player_stats.runs_recorded = player_data.runs_finished;
+
+    if run.score > player_data.best_score {
+        player_data.best_score = run.score;
+    }
+
+    let entry = LeaderboardEntry {
+        player: run.authority,
+        name: leaderboard_name(&player_data.name),
+        score: run.score,
+        timestamp: now,
+    };
+    // a run an admin had to stop can't be trusted to rank
+    if end_reason != RunEndReason::ForceFinished {
+        if run.daily_day != 0 {
+            daily_leaderboard
+                .ok_or(GameErrorCode::DailyLeaderboardRequired)?
+                .submit(entry.clone());
+        }
+        // every difficulty competes in the season, its score already scaled by the difficulty
+        if season.is_active(now) {
+            season_leaderboard.submit(entry.clone());
+        }
+        leaderboard.submit(entry);
+    }
+
+    player_data.set_run_active(run.run_slot, false);
+    run.status = RunStatus::Idle;
+    run.thread = Pubkey::default();
+    run.score = 0;
+    run.score_breakdown = ScoreBreakdown::default();
+    run.experience = 0;
+    run.last_character_id = 0;
+    run.last_card_id = 0;
+
+    Ok(())
+}
+
+/// The tutorial leftovers, which change the score or the thread with no lifecycle checks. They are
+/// kept out of the program module, and so out of the IDL, and are only compiled into builds with
+/// the `debug-instructions` feature; the others refuse them with `DebugInstructionsDisabled`.
+pub const DEBUG_INSTRUCTIONS: [&str; 6] = [
+    "increment",
+    "reset",
+    "start_thread",
+    "pause_thread",
+    "resume_thread",
+    "delete_thread",
+];
+
+/// The debug instruction `discriminator` addresses, if any, matched the way Anchor names its own.
+pub fn debug_instruction_name(discriminator: &[u8]) -> Option<&'static str> {
+    DEBUG_INSTRUCTIONS.iter().copied().find(|name| {
+        hash(format!("global:{}", name).as_bytes()).to_bytes()[..8] == *discriminator
+    })
+}
+
+/// Seed distinguishing a player's run slots. Slot 0 adds nothing, so it keeps the address the
+/// single run had before there were slots.
+pub fn run_slot_seed(run_slot: &u8) -> &[u8] {
+    if *run_slot == 0 {
+        &[]
+    } else {
+        std::slice::from_ref(run_slot)
+    }
+}
+
+pub fn run_slot_bit(run_slot: u8) -> u8 {
+    1u8.checked_shl(run_slot as u32).unwrap_or(0)
+}
+
+/// Reallocs a program account to at least `space` bytes, zero-filling the new tail so fields
+/// appended since the account was written read as their defaults, and tops up its rent from
+/// `payer`.
+pub fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    if account.data_len() >= space {
+        return Ok(());
+    }
+
+    let rent_due = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+    account.realloc(space, true)?;
+    Ok(())
+}
+
+/// Creates a PDA owned by this program the way Anchor's `init` does, including when the address was
+/// already sent lamports, which would make a plain `create_account` fail.
+pub fn create_program_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            rent,
+            space as u64,
+            &ID,
+        );
+    }
+
+    let rent_due = rent.saturating_sub(account.lamports());
+    if rent_due > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            anchor_lang::system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        space as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            anchor_lang::system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        &ID,
+    )
+}
+
+/// Lamports to seed a run's thread with: a fee for every tick of `expected_waves` waves plus a
+/// fixed buffer. `top_up_run_thread` covers runs that outlast the estimate.
+pub fn thread_funding_lamports(expected_waves: u16, difficulty: Difficulty) -> Result<u64> {
+    require!(
+        expected_waves > 0 && expected_waves <= MAX_EXPECTED_RUN_WAVES,
+        GameErrorCode::InvalidExpectedWaves
+    );
+    // the thread fires on a clock, not per tick, so slower ticks make for longer waves
+    let wave_ms =
+        u64::from(TICKS_PER_WAVE) * u64::from(TICK_INTERVAL_MS_BY_DIFFICULTY[difficulty as usize]);
+    let executions_per_wave = (wave_ms + 999) / 1000 / u64::from(THREAD_INTERVAL_SECONDS);
+    let executions = u64::from(expected_waves) * executions_per_wave;
+    executions
+        .checked_mul(THREAD_FEE_LAMPORTS)
+        .and_then(|fees| fees.checked_add(THREAD_FUNDING_BUFFER_LAMPORTS))
+        .ok_or_else(|| error!(GameErrorCode::CounterOverflow))
+}
+
+/// The `auto_finish_run` a run's thread chains once its heroes are gone. The record address comes
+/// from the player's current `runs_finished`, see `auto_finish_run` for when that goes stale.
+pub fn auto_finish_instruction(
+    run: &Account<RunData>,
+    player_data: &Account<PlayerData>,
+    thread: Pubkey,
+    thread_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: crate::accounts::AutoFinishRun {
+            run: run.key(),
+            player_data: player_data.key(),
+            player: run.authority,
+            thread,
+            thread_authority,
+            leaderboard: Pubkey::find_program_address(
+                &[LEADERBOARD_SEED, run.difficulty.seed()],
+                &ID,
+            )
+            .0,
+            season: Pubkey::find_program_address(&[SEASON_SEED], &ID).0,
+            season_leaderboard: Pubkey::find_program_address(
+                &[SEASON_SEED, LEADERBOARD_SEED],
+                &ID,
+            )
+            .0,
+            daily_leaderboard: (run.daily_day != 0).then(|| {
+                Pubkey::find_program_address(
+                    &[
+                        DAILY_CHALLENGE_SEED,
+                        run.daily_day.to_le_bytes().as_ref(),
+                        LEADERBOARD_SEED,
+                    ],
+                    &ID,
+                )
+                .0
+            }),
+            run_record: Pubkey::find_program_address(
+                &[
+                    RUN_RECORD_SEED,
+                    run.authority.as_ref(),
+                    player_data.runs_finished.to_le_bytes().as_ref(),
+                ],
+                &ID,
+            )
+            .0,
+            player_stats: Pubkey::find_program_address(
+                &[PLAYER_STATS_SEED, run.authority.as_ref()],
+                &ID,
+            )
+            .0,
+            crank_vault: Pubkey::find_program_address(
+                &[CRANK_VAULT_SEED, run.key().as_ref()],
+                &ID,
+            )
+            .0,
+            payer: clockwork_sdk::utils::PAYER_PUBKEY,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: crate::instruction::AutoFinishRun {}.data(),
+    }
+}
+
+pub fn thread_trigger(interval_seconds: u8) -> Trigger {
+    Trigger::Cron {
+        schedule: format!("*/{} * * * * * *", interval_seconds),
+        skippable: true,
+    }
+}
+
+/// Pays `amount` out of a run's crank vault, a system account PDA signed for with `signer_seeds`.
+pub fn withdraw_from_crank_vault<'info>(
+    crank_vault: &SystemAccount<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    signer_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    anchor_lang::system_program::transfer(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: crank_vault.to_account_info(),
+                to: to.clone(),
+            },
+            &[signer_seeds],
+        ),
+        amount,
+    )
+}
+
+/// Simulates every tick that has come due since `last_tick_at`, up to `MAX_TICKS_PER_CALL`, so
+/// game time follows the clock however reliably the run is cranked. Returns the number processed.
+pub fn catch_up(run: &mut RunData, now: i64) -> Result<u32> {
+    let due = run.ticks_due(now).min(MAX_TICKS_PER_CALL);
+    let mut processed = 0;
+    // a defeated run stands still until it is finished
+    while processed < due && run.heroes_alive() > 0 {
+        process_tick(run)?;
+        processed += 1;
+    }
+    // only move forward by what was processed, so the rest is picked up by the next call
+    run.advance_ticks(processed);
+    Ok(processed)
+}
+
+/// Simulates the ticks still due before a run ends naturally, so finishing can't skip a backlog
+/// in which the heroes would have died. Fails if more is due than one call simulates; `tick`
+/// works the rest off first.
+pub fn settle_run(run: &mut RunData, now: i64) -> Result<()> {
+    // a defeated run has nothing left to simulate
+    if run.heroes_alive() == 0 {
+        return Ok(());
+    }
+    let now = run.game_now(now);
+    catch_up(run, now)?;
+    require!(
+        run.heroes_alive() == 0 || run.ticks_due(now) == 0,
+        GameErrorCode::TicksOverdue
+    );
+    Ok(())
+}
+
+/// Advances the game by one tick: cooldowns, attacks, movement and enemy spawns. Once the last
+/// hero has fallen there is nothing left to survive, so the run no longer changes or scores.
+pub fn process_tick(run: &mut RunData) -> Result<()> {
+    if run.heroes_alive() == 0 {
+        return Ok(());
+    }
+    run.ticks = run
+        .ticks
+        .checked_add(1)
+        .ok_or(GameErrorCode::CounterOverflow)?;
+    run.score_breakdown.survival = run
+        .score_breakdown
+        .survival
+        .saturating_add(SURVIVAL_SCORE_PER_TICK);
+    if run.ticks % TICKS_PER_WAVE == 0 {
+        run.wave = run.wave.saturating_add(1);
+        run.score_breakdown.waves = run.score_breakdown.waves.saturating_add(WAVE_CLEARED_SCORE);
+    }
+
+    let mut slots_clone = run.slots.clone();
+    let n = run.slots.len();
+
+    //refresh cooldowns
+    for i in 0..n {
+        let slot = slots_clone[i];
+
+        match slot {
+            Some(mut character_info) => {
+                //cooldowns
+                let mut perform_action = false;
+                let mut new_cooldown_timer = character_info.cooldown_timer.saturating_sub(1);
+                if new_cooldown_timer == 0 {
+                    new_cooldown_timer = character_info.cooldown;
+                    perform_action = true;
+                }
+                else{
+                    character_info.state = 0;
+                }
+                character_info.cooldown_timer = new_cooldown_timer;
+
+                slots_clone[i] = Some(character_info);
+
+                if perform_action {
+                    //perform actions for zombies
+                    if character_info.alignment == 1 {
+                        if i > 0 {
+                            //if there is somebody to the left
+                            if let Some(mut attacked_character) = slots_clone[i - 1] {
+                                //if it is a hero
+                                if attacked_character.alignment == 0 {
+                                    character_info.state = 1;
+                                    slots_clone[i] = Some(character_info);
+                                    //attack the hero
+                                    run.record_hit_on_hero(
+                                        &attacked_character,
+                                        character_info.attack_damage,
+                                    );
+                                    if character_info.attack_damage >= attacked_character.health
+                                    {
+                                        slots_clone[i - 1] = None;
+                                    } else {
+                                        let new_health = attacked_character
+                                            .health
+                                            .saturating_sub(character_info.attack_damage);
+                                        attacked_character.health = new_health;
+                                        slots_clone[i - 1] = Some(attacked_character);
+                                    }
+                                }
+                            } else {
+                                //move left
+                                slots_clone[i] = None;
+                                character_info.state = 2;
+                                slots_clone[i - 1] = Some(character_info);
+                            }
+                        }
+                    }
+                    //perform actions for heroes
+                    else if character_info.alignment == 0 {
+                        match character_info.character_type {
+                            2 => {
+                                //if there is somebody to the right
+                                if let Some(Some(mut attacked_character)) =
+                                    slots_clone.get(i + 1).copied()
+                                {
+                                    //if it is a hero
+                                    if attacked_character.alignment == 1 {
+                                        //attack the hero
+                                        character_info.state = 1;
+                                        slots_clone[i] = Some(character_info);
+                                        run.record_hit_on_enemy(
+                                            &attacked_character,
+                                            character_info.attack_damage,
+                                        );
+                                        if character_info.attack_damage
+                                            >= attacked_character.health
+                                        {
+                                            slots_clone[i + 1] = None;
+                                            let new_experience = run.experience.saturating_add(1);
+                                            run.experience = new_experience;
+                                        } else {
+                                            let new_health = attacked_character
+                                                .health
+                                                .saturating_sub(character_info.attack_damage);
+                                            attacked_character.health = new_health;
+                                            slots_clone[i + 1] = Some(attacked_character);
+                                        }
+                                    }
+                                }
+                            }
+                            1 => {
+                                for a in 4..7 {
+                                    //nobody to shoot if the slot is empty
+                                    if let Some(mut attacked_character) = slots_clone[a] {
+                                        //if it is a hero
+                                        if attacked_character.alignment == 1 {
+                                            //attack the hero
+                                            character_info.state = 1;
+                                            slots_clone[i] = Some(character_info);
+                                            run.record_hit_on_enemy(
+                                                &attacked_character,
+                                                character_info.attack_damage,
+                                            );
+                                            if character_info.attack_damage
+                                                >= attacked_character.health
+                                            {
+                                                slots_clone[a] = None;
+                                                let new_experience = run.experience.saturating_add(1);
+                                                run.experience = new_experience;
+                                            } else {
+                                                let new_health = attacked_character
+                                                    .health
+                                                    .saturating_sub(character_info.attack_damage);
+                                                attacked_character.health = new_health;
+                                                slots_clone[a] = Some(attacked_character);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            0 => {
+                                if let Some(mut attacked_character) = slots_clone[6] {
+                                    if attacked_character.alignment == 1 {
+                                        //attack
+                                        character_info.state = 1;
+                                        slots_clone[i] = Some(character_info);
+                                        run.record_hit_on_enemy(
+                                            &attacked_character,
+                                            character_info.attack_damage,
+                                        );
+                                        if character_info.attack_damage
+                                            >= attacked_character.health
+                                        {
+                                            slots_clone[6] = None;
+                                            let new_experience = run.experience.saturating_add(1);
+                                            run.experience = new_experience;
+                                        } else {
+                                            let new_health = attacked_character
+                                                .health
+                                                .saturating_sub(character_info.attack_damage);
+                                            attacked_character.health = new_health;
+                                            slots_clone[6] = Some(attacked_character);
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if slots_clone[6].is_none() {
+        let random_enemy_type = run.next_enemy_type();
+
+        // ids only need to be unique among the characters on the board
+        let new_last_character_id = run.last_character_id.wrapping_add(1);
+        run.last_character_id = new_last_character_id;
+
+        slots_clone[6] = Some(run.new_enemy(new_last_character_id, random_enemy_type));
+    }
+
+    run.slots = slots_clone;
+    run.score = run.scaled_score();
+
+    Ok(())
 }
 
 pub fn xorshift64(seed: u64) -> u64 {
@@ -871,3 +3984,397 @@ pub fn xorshift64(seed: u64) -> u64 {
     x ^= x << 17;
     x
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_run() -> RunData {
+        RunData::try_deserialize_unchecked(&mut &vec![0u8; RunData::SPACE][..]).unwrap()
+    }
+
+    fn stored(run: &RunData) -> Vec<u8> {
+        let mut data = vec![0u8; RunData::SPACE];
+        run.try_serialize(&mut &mut data[..]).unwrap();
+        data
+    }
+
+    fn legacy_run() -> LegacyRunData {
+        let hero = CharacterInfoV5 {
+            id: 1,
+            alignment: 0,
+            character_type: 2,
+            cooldown: 3,
+            cooldown_timer: 1,
+            max_health: 200,
+            health: 150,
+            attack_damage: 25,
+            state: 1,
+        };
+        let enemy = CharacterInfoV5 {
+            id: 9,
+            alignment: 1,
+            character_type: 5,
+            health: 40,
+            ..hero
+        };
+        LegacyRunData {
+            authority: Pubkey::new_unique(),
+            score: 1234,
+            experience: 56,
+            slots: [Some(hero), None, Some(hero), None, None, None, Some(enemy)],
+            last_character_id: 9,
+            cards: [
+                CardInfo { id: 4, card_type: 0 },
+                CardInfo { id: 5, card_type: 2 },
+                CardInfo { id: 6, card_type: 1 },
+            ],
+            last_card_id: 6,
+        }
+    }
+
+    /// A legacy account as stored, 0xAB past its fields, grown with a zeroed tail.
+    fn stored_legacy(legacy: &LegacyRunData) -> Vec<u8> {
+        let mut data = vec![0xAB; RunData::LEGACY_SPACE];
+        data[..8].copy_from_slice(&RunData::discriminator());
+        legacy.serialize(&mut &mut data[8..]).unwrap();
+        data.resize(RunData::SPACE, 0);
+        data
+    }
+
+    #[test]
+    fn migrates_legacy_run() {
+        let legacy = legacy_run();
+        let mut data = stored_legacy(&legacy);
+        upgrade_run_data(&mut data, RunData::LEGACY_SPACE, 3).unwrap();
+
+        let mut expected = blank_run();
+        expected.authority = legacy.authority;
+        expected.score = legacy.score;
+        expected.experience = legacy.experience;
+        expected.slots = legacy.slots.map(|slot| slot.map(Into::into));
+        expected.last_character_id = legacy.last_character_id;
+        expected.cards = legacy.cards;
+        expected.last_card_id = legacy.last_card_id;
+        expected.status = RunStatus::Idle;
+        expected.version = RunData::VERSION;
+        expected.run_slot = 3;
+        expected.spawn_rng_state = 1;
+        expected.difficulty = Difficulty::Normal;
+        assert!(data == stored(&expected));
+
+        let run = RunData::try_deserialize(&mut &data[..]).unwrap();
+        let hero = run.slots[2].unwrap();
+        assert_eq!(
+            (hero.cooldown, hero.max_health, hero.health, hero.attack_damage),
+            (3, 200, 150, 25)
+        );
+        assert!(run.slots[1].is_none());
+        assert_eq!(run.slots[6].unwrap().health, 40);
+    }
+
+    #[test]
+    fn migrates_legacy_run_with_full_slots() {
+        let mut legacy = legacy_run();
+        let hero = legacy.slots[0];
+        legacy.slots = [hero; 7];
+        let mut data = stored_legacy(&legacy);
+        upgrade_run_data(&mut data, RunData::LEGACY_SPACE, 0).unwrap();
+
+        let run = RunData::try_deserialize(&mut &data[..]).unwrap();
+        assert!(run.slots.iter().all(|slot| slot.map(|c| c.id) == Some(1)));
+        assert_eq!(run.last_card_id, 6);
+        assert_eq!(run.seed, 0);
+        assert_eq!(run.version, RunData::VERSION);
+    }
+
+    #[test]
+    fn rejects_legacy_run_of_another_account_type() {
+        let mut data = stored_legacy(&legacy_run());
+        data[..8].copy_from_slice(&PlayerData::discriminator());
+        assert!(upgrade_run_data(&mut data, RunData::LEGACY_SPACE, 0).is_err());
+    }
+
+    fn sample_slots() -> [Option<CharacterInfoV5>; 7] {
+        let hero = legacy_run().slots[0].unwrap();
+        let enemy = CharacterInfoV5 {
+            id: 12,
+            alignment: 1,
+            character_type: 6,
+            cooldown: 4,
+            cooldown_timer: 2,
+            max_health: 90,
+            health: 7,
+            attack_damage: 30,
+            state: 2,
+        };
+        [None, Some(hero), None, Some(enemy), None, Some(enemy), Some(hero)]
+    }
+
+    /// A run with every field off its default, holding `slots`.
+    fn sample_run(version: u8, slots: [Option<CharacterInfoV5>; 7]) -> RunData {
+        let mut run = blank_run();
+        run.authority = Pubkey::new_unique();
+        run.score = 9876;
+        run.experience = 31;
+        run.slots = slots.map(|slot| slot.map(Into::into));
+        run.last_character_id = 12;
+        run.cards = legacy_run().cards;
+        run.last_card_id = 6;
+        run.seed = 11;
+        run.rng_state = 22;
+        run.started_at = 1_700_000_000;
+        run.ticks = 345;
+        run.wave = 4;
+        run.kills = 17;
+        run.kills_by_type = [1, 2, 3, 4, 5, 6, 7];
+        run.damage_dealt = 800;
+        run.damage_taken = 600;
+        run.cards_played_by_type = [3, 2, 1];
+        run.heroes_lost_by_type = [1, 0, 2];
+        run.status = RunStatus::Paused;
+        run.thread = Pubkey::new_unique();
+        run.last_tick_at = 1_700_000_300;
+        run.thread_interval = 2;
+        run.paused_at = 1_700_000_310;
+        run.pause_used = 40;
+        run.expires_at = 1_700_086_400;
+        run.score_breakdown = ScoreBreakdown {
+            kills: 100,
+            waves: 200,
+            survival: 300,
+            extraction: 400,
+        };
+        run.version = version;
+        run.run_slot = 2;
+        run.spawn_rng_state = 33;
+        run.daily_day = 19_000;
+        run.modifiers = ChallengeModifiers {
+            enemy_health_bonus: 10,
+            enemy_attack_bonus: 20,
+            starting_experience: 5,
+        };
+        run.difficulty = Difficulty::Hard;
+        run.tick_carry_ms = 250;
+        run.mutators = 0b11;
+        run.skipped_pause_at = 1_600_000_000;
+        run
+    }
+
+    /// `run` as stored before version 8: its slots written as `slots`, without
+    /// `skipped_pause_at`, 0xAB up to `stored_len` and grown with a zeroed tail.
+    fn stored_before_v8<T: AnchorSerialize>(
+        run: &RunData,
+        slots: [Option<T>; 7],
+        stored_len: usize,
+    ) -> Vec<u8> {
+        let current = stored(run);
+        let slots_end = RunData::SLOTS_OFFSET + run.slots.try_to_vec().unwrap().len();
+        // the discriminator and every field but the trailing `skipped_pause_at`
+        let fields_end = run.try_to_vec().unwrap().len();
+
+        let mut data = current[..RunData::SLOTS_OFFSET].to_vec();
+        data.extend(slots.try_to_vec().unwrap());
+        data.extend_from_slice(&current[slots_end..fields_end]);
+        assert!(data.len() <= stored_len);
+        data.resize(stored_len, 0xAB);
+        data.resize(RunData::SPACE, 0);
+        data
+    }
+
+    fn assert_migrated(data: &[u8], expected: &RunData) {
+        let run = RunData::try_deserialize(&mut &data[..]).unwrap();
+        assert!(stored(&run) == stored(expected));
+    }
+
+    fn expected_after_migrate(run: &RunData) -> RunData {
+        let mut expected = RunData::try_deserialize(&mut &stored(run)[..]).unwrap();
+        expected.version = RunData::VERSION;
+        expected.skipped_pause_at = 0;
+        expected
+    }
+
+    #[test]
+    fn derives_stored_sizes_from_slot_layouts() {
+        assert_eq!(RunData::SPACE, 368);
+        assert_eq!(RunData::V6_SPACE, 346);
+        assert_eq!(RunData::V5_SPACE, 325);
+        assert_eq!(
+            vec![Some(CharacterInfo::default())].try_to_vec().unwrap().len(),
+            4 + 1 + CharacterInfo::SPACE
+        );
+    }
+
+    #[test]
+    fn migrates_v5_run() {
+        for slots in [sample_slots(), [legacy_run().slots[0]; 7], [None; 7]] {
+            let run = sample_run(5, slots);
+            let mut data = stored_before_v8(&run, slots, RunData::V5_SPACE);
+            upgrade_run_data(&mut data, RunData::V5_SPACE, 0).unwrap();
+            assert_migrated(&data, &expected_after_migrate(&run));
+        }
+    }
+
+    #[test]
+    fn migrates_v6_run() {
+        for slots in [sample_slots(), [legacy_run().slots[0]; 7], [None; 7]] {
+            let run = sample_run(6, slots);
+            let v6_slots = slots.map(|slot| {
+                slot.map(|c| CharacterInfoV6 {
+                    id: c.id,
+                    alignment: c.alignment,
+                    character_type: c.character_type,
+                    cooldown: c.cooldown,
+                    cooldown_timer: c.cooldown_timer,
+                    max_health: c.max_health as u16,
+                    health: c.health as u16,
+                    attack_damage: c.attack_damage as u16,
+                    state: c.state,
+                })
+            });
+            let mut data = stored_before_v8(&run, v6_slots, RunData::V6_SPACE);
+            upgrade_run_data(&mut data, RunData::V6_SPACE, 0).unwrap();
+            assert_migrated(&data, &expected_after_migrate(&run));
+        }
+    }
+
+    #[test]
+    fn migrates_v7_run() {
+        for slots in [sample_slots(), [legacy_run().slots[0]; 7], [None; 7]] {
+            let run = sample_run(7, slots);
+            let v7_len = RunData::SPACE - 8;
+            let mut data = stored_before_v8(&run, run.slots, v7_len);
+            upgrade_run_data(&mut data, v7_len, 0).unwrap();
+            assert_migrated(&data, &expected_after_migrate(&run));
+        }
+    }
+
+    #[test]
+    fn leaves_current_run_untouched() {
+        let run = sample_run(RunData::VERSION, sample_slots());
+        let mut data = stored(&run);
+        upgrade_run_data(&mut data, RunData::SPACE, 0).unwrap();
+        assert!(data == stored(&run));
+    }
+
+    #[test]
+    fn counts_ticks_from_the_carried_time() {
+        let mut run = blank_run();
+        run.difficulty = Difficulty::Hard;
+        run.last_tick_at = 1_000;
+
+        // 750ms ticks: three of them end 250ms into the third second
+        assert_eq!(run.ticks_due(1_002), 2);
+        run.advance_ticks(2);
+        assert_eq!((run.last_tick_at, run.tick_carry_ms), (1_001, 500));
+        assert_eq!(run.ticks_due(1_001), 0);
+        assert_eq!(run.ticks_due(1_002), 0);
+        assert_eq!(run.ticks_due(1_003), 2);
+        run.advance_ticks(2);
+        assert_eq!((run.last_tick_at, run.tick_carry_ms), (1_003, 0));
+
+        // the same stretch of time yields the same ticks however it is cranked
+        let mut cranked = blank_run();
+        cranked.difficulty = Difficulty::Hard;
+        cranked.last_tick_at = 1_000;
+        for now in 1_001..=1_030 {
+            let due = cranked.ticks_due(now);
+            cranked.advance_ticks(due);
+        }
+        assert_eq!(cranked.ticks_due(1_030), 0);
+        assert_eq!((cranked.last_tick_at, cranked.tick_carry_ms), (1_030, 0));
+
+        // a clock behind the carried time owes nothing
+        run.tick_carry_ms = 900;
+        assert_eq!(run.ticks_due(run.last_tick_at), 0);
+    }
+
+    #[test]
+    fn funds_threads_for_the_difficulty_wave_length() {
+        let fees = |waves, difficulty| {
+            (thread_funding_lamports(waves, difficulty).unwrap() - THREAD_FUNDING_BUFFER_LAMPORTS)
+                / THREAD_FEE_LAMPORTS
+        };
+        assert_eq!(fees(10, Difficulty::Easy), 450);
+        assert_eq!(fees(10, Difficulty::Normal), 300);
+        assert_eq!(fees(10, Difficulty::Hard), 230);
+        assert!(thread_funding_lamports(0, Difficulty::Normal).is_err());
+    }
+
+    #[test]
+    fn charges_double_for_cards_with_expensive_cards() {
+        let mut run = blank_run();
+        run.experience = 3;
+        run.pay_for_card(2).unwrap();
+        assert_eq!(run.experience, 0);
+
+        run.mutators = MUTATOR_EXPENSIVE_CARDS;
+        run.experience = 5;
+        assert_eq!(
+            run.pay_for_card(2).unwrap_err(),
+            GameErrorCode::InsufficientExperience.into()
+        );
+        assert_eq!(run.experience, 5);
+        run.pay_for_card(1).unwrap();
+        assert_eq!(run.experience, 1);
+    }
+
+    #[test]
+    fn scores_nothing_once_the_heroes_are_gone() {
+        let mut run = blank_run();
+        run.slots[6] = Some(CharacterInfo::new(1, 1, 3));
+        run.last_tick_at = 1_000;
+        run.difficulty = Difficulty::Normal;
+        for _ in 0..TICKS_PER_WAVE {
+            process_tick(&mut run).unwrap();
+        }
+        assert_eq!(run.ticks, 0);
+        assert_eq!(run.wave, 0);
+        assert!(run.score_breakdown == ScoreBreakdown::default());
+
+        assert_eq!(catch_up(&mut run, 1_000 + 60).unwrap(), 0);
+        assert_eq!(run.last_tick_at, 1_000);
+        assert!(run.score_breakdown == ScoreBreakdown::default());
+    }
+
+    #[test]
+    fn scores_survival_while_a_hero_stands() {
+        let mut run = blank_run();
+        run.slots[0] = Some(CharacterInfo::new(1, 0, 0));
+        run.difficulty = Difficulty::Normal;
+        run.rng_state = 1;
+        run.spawn_rng_state = 1;
+        process_tick(&mut run).unwrap();
+        assert_eq!(run.ticks, 1);
+        assert_eq!(run.score_breakdown.survival, SURVIVAL_SCORE_PER_TICK);
+    }
+
+    #[test]
+    fn routes_only_debug_instructions_to_the_fallback() {
+        let increment = hash(b"global:increment").to_bytes();
+        assert_eq!(debug_instruction_name(&increment[..8]), Some("increment"));
+        assert_eq!(debug_instruction_name(&instruction::Tick::DISCRIMINATOR), None);
+
+        let result = extracto_program::fallback(&ID, &[], &[0; 4]);
+        assert_eq!(
+            result.unwrap_err(),
+            anchor_lang::error::ErrorCode::InstructionMissing.into()
+        );
+        let result = extracto_program::fallback(&ID, &[], &[0; 8]);
+        assert_eq!(
+            result.unwrap_err(),
+            anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into()
+        );
+    }
+
+    #[cfg(not(feature = "debug-instructions"))]
+    #[test]
+    fn refuses_debug_instructions_without_the_feature() {
+        let reset = hash(b"global:reset").to_bytes();
+        let result = extracto_program::fallback(&ID, &[], &reset[..8]);
+        assert_eq!(
+            result.unwrap_err(),
+            GameErrorCode::DebugInstructionsDisabled.into()
+        );
+    }
+}
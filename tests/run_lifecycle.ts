@@ -0,0 +1,457 @@
+import * as anchor from "@coral-xyz/anchor"
+import { Program } from "@coral-xyz/anchor"
+import { expect } from "chai"
+import { ExtractoProgram } from "../target/types/extracto_program"
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram, Transaction } from "@solana/web3.js"
+import { createHash } from "crypto"
+
+const CLOCKWORK_THREAD_PROGRAM_ID = new PublicKey("CLoCKyJ6DXBJqqu2VWx9RLbgnwwR6BMHHuyasVmfMzBh")
+
+describe("run lifecycle", () => {
+    const provider = anchor.AnchorProvider.env()
+    anchor.setProvider(provider)
+    const program = anchor.workspace.ExtractoProgram as Program<ExtractoProgram>
+
+    const player = Keypair.generate()
+    const name = "lc" + Math.random().toString(36).slice(2, 10)
+
+    const pda = (...seeds: Buffer[]) => PublicKey.findProgramAddressSync(seeds, program.programId)[0]
+
+    const playerData = pda(Buffer.from("player"), player.publicKey.toBuffer())
+    const run = pda(Buffer.from("run"), player.publicKey.toBuffer())
+    const threadAuthority = pda(Buffer.from("thread_authority"), player.publicKey.toBuffer())
+    const leaderboard = pda(Buffer.from("leaderboard"))
+    const hardLeaderboard = pda(Buffer.from("leaderboard"), Buffer.from("hard"))
+    const season = pda(Buffer.from("season"))
+    const seasonLeaderboard = pda(Buffer.from("season"), Buffer.from("leaderboard"))
+    const playerStats = pda(Buffer.from("player_stats"), player.publicKey.toBuffer())
+    const crankVault = pda(Buffer.from("crank_vault"), run.toBuffer())
+    const programAdmin = pda(Buffer.from("program_admin"))
+    const nameRegistry = pda(
+        Buffer.from("name"),
+        createHash("sha256").update(name.toLowerCase()).digest()
+    )
+
+    const threadAddress = (threadId: string) =>
+        PublicKey.findProgramAddressSync(
+            [Buffer.from("thread"), threadAuthority.toBuffer(), Buffer.from(threadId)],
+            CLOCKWORK_THREAD_PROGRAM_ID
+        )[0]
+
+    const runRecord = async () => {
+        const data = await program.account.playerData.fetch(playerData)
+        const index = Buffer.alloc(4)
+        index.writeUInt32LE(data.runsFinished)
+        return pda(Buffer.from("run_record"), player.publicKey.toBuffer(), index)
+    }
+
+    const today = () => Math.floor(Date.now() / 1000 / (24 * 60 * 60))
+    const dayBytes = (day: number) => {
+        const bytes = Buffer.alloc(4)
+        bytes.writeUInt32LE(day)
+        return bytes
+    }
+    const dailyChallenge = (day: number) => pda(Buffer.from("daily_challenge"), dayBytes(day))
+    const dailyLeaderboard = (day: number) =>
+        pda(Buffer.from("daily_challenge"), dayBytes(day), Buffer.from("leaderboard"))
+
+    // a null thread id starts a cranked run
+    const startNewRun = (
+        threadId: string | null,
+        challenge: PublicKey | null = null,
+        runAccount = run,
+        difficulty: anchor.IdlTypes<ExtractoProgram>["Difficulty"] = { normal: {} },
+        mutators = 0
+    ) =>
+        program.methods
+            .startNewRun(Buffer.from(threadId ?? "unused"), null, difficulty, mutators)
+            .accounts({
+                run: runAccount,
+                playerData,
+                player: player.publicKey,
+                clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+                systemProgram: SystemProgram.programId,
+                thread: threadId === null ? null : threadAddress(threadId),
+                threadAuthority,
+                programAdmin,
+                dailyChallenge: challenge,
+            })
+            .signers([player])
+            .rpc()
+
+    const finishRun = async (
+        threadId: string | null,
+        board: PublicKey | null = null,
+        rankedOn = leaderboard,
+        runAccount = run
+    ) =>
+        program.methods
+            .finishRun()
+            .accounts({
+                run: runAccount,
+                playerData,
+                player: player.publicKey,
+                clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+                thread: threadId === null ? null : threadAddress(threadId),
+                threadAuthority,
+                leaderboard: rankedOn,
+                season,
+                seasonLeaderboard,
+                dailyLeaderboard: board,
+                runRecord: await runRecord(),
+                playerStats,
+                crankVault: pda(Buffer.from("crank_vault"), runAccount.toBuffer()),
+                programAdmin,
+                systemProgram: SystemProgram.programId,
+            })
+            .signers([player])
+            .rpc()
+
+    const initPlayer = () =>
+        program.methods
+            .initPlayer(name)
+            .accounts({ playerData, run, playerStats, nameRegistry, player: player.publicKey })
+            .signers([player])
+            .rpc()
+
+    const upgrade = () =>
+        program.methods
+            .upgrade(0, 0)
+            .accounts({ run, programAdmin, user: player.publicKey, sessionToken: null })
+            .signers([player])
+            .rpc()
+
+    const pauseAccounts = (threadId: string) => ({
+        run,
+        user: player.publicKey,
+        sessionToken: null,
+        clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+        thread: threadAddress(threadId),
+        threadAuthority,
+    })
+
+    const expectError = async (promise: Promise<unknown>, code: string) => {
+        try {
+            await promise
+        } catch (err) {
+            expect(err).to.be.instanceOf(anchor.AnchorError)
+            expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code)
+            return
+        }
+        expect.fail(`expected ${code}`)
+    }
+
+    before(async () => {
+        await provider.sendAndConfirm(
+            new Transaction().add(
+                SystemProgram.transfer({
+                    fromPubkey: provider.wallet.publicKey,
+                    toPubkey: player.publicKey,
+                    lamports: LAMPORTS_PER_SOL / 2,
+                })
+            )
+        )
+
+        if ((await provider.connection.getAccountInfo(leaderboard)) === null) {
+            await program.methods.initLeaderboard({ normal: {} }).accounts({ leaderboard }).rpc()
+        }
+        if ((await provider.connection.getAccountInfo(hardLeaderboard)) === null) {
+            await program.methods
+                .initLeaderboard({ hard: {} })
+                .accounts({ leaderboard: hardLeaderboard })
+                .rpc()
+        }
+        if ((await provider.connection.getAccountInfo(programAdmin)) === null) {
+            const [programData] = PublicKey.findProgramAddressSync(
+                [program.programId.toBuffer()],
+                new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+            )
+            await program.methods
+                .initProgramAdmin()
+                .accounts({ programAdmin, program: program.programId, programData })
+                .rpc()
+        }
+        if ((await provider.connection.getAccountInfo(season)) === null) {
+            await program.methods
+                .initSeason(new anchor.BN(30 * 24 * 60 * 60))
+                .accounts({ season, seasonLeaderboard, programAdmin, admin: provider.wallet.publicKey })
+                .rpc()
+        }
+
+        await initPlayer()
+    })
+
+    it("leaves current accounts untouched on migrate", async () => {
+        await program.methods.migrateRun(0).accounts({ run, player: player.publicKey }).signers([player]).rpc()
+        await program.methods
+            .migratePlayer()
+            .accounts({ playerData, playerStats, player: player.publicKey })
+            .signers([player])
+            .rpc()
+
+        expect((await program.account.runData.fetch(run)).version).to.equal(8)
+        expect((await program.account.playerData.fetch(playerData)).version).to.equal(3)
+    })
+
+    it("renames to a case-only variant in place", async () => {
+        await program.methods
+            .renamePlayer(name.toUpperCase())
+            .accounts({
+                playerData,
+                oldNameRegistry: nameRegistry,
+                newNameRegistry: nameRegistry,
+                player: player.publicKey,
+            })
+            .signers([player])
+            .rpc()
+
+        expect((await program.account.playerData.fetch(playerData)).name).to.equal(name.toUpperCase())
+        const registry = await program.account.nameRegistry.fetch(nameRegistry)
+        expect(registry.player.equals(player.publicKey)).to.equal(true)
+        expect(registry.name).to.equal(name.toUpperCase())
+    })
+
+    it("rejects a name someone already holds, in any case", async () => {
+        const other = Keypair.generate()
+        await provider.sendAndConfirm(
+            new Transaction().add(
+                SystemProgram.transfer({
+                    fromPubkey: provider.wallet.publicKey,
+                    toPubkey: other.publicKey,
+                    lamports: LAMPORTS_PER_SOL / 10,
+                })
+            )
+        )
+        const initOther = (otherName: string) =>
+            program.methods
+                .initPlayer(otherName)
+                .accounts({
+                    playerData: pda(Buffer.from("player"), other.publicKey.toBuffer()),
+                    run: pda(Buffer.from("run"), other.publicKey.toBuffer()),
+                    playerStats: pda(Buffer.from("player_stats"), other.publicKey.toBuffer()),
+                    nameRegistry,
+                    player: other.publicKey,
+                })
+                .signers([other])
+                .rpc()
+
+        // the player renamed to upper case above
+        await expectError(initOther(name.toUpperCase()), "NameTaken")
+        await expectError(initOther(name), "NameTaken")
+        expect((await program.account.nameRegistry.fetch(nameRegistry)).player.equals(player.publicKey)).to.equal(
+            true
+        )
+    })
+
+    it("rejects upgrade before a run is started", async () => {
+        await expectError(upgrade(), "NotInRun")
+    })
+
+    it("rejects a second start_new_run while a run is active", async () => {
+        await startNewRun("lifecycle_1")
+        await expectError(startNewRun("lifecycle_2"), "AlreadyInRun")
+
+        const account = await program.account.runData.fetch(run)
+        expect(account.status).to.deep.equal({ active: {} })
+    })
+
+    it("rejects a card the run has no experience for", async () => {
+        expect((await program.account.runData.fetch(run)).experience).to.equal(0)
+        await expectError(upgrade(), "InsufficientExperience")
+    })
+
+    it("rejects close_player while a run is active", async () => {
+        await expectError(
+            program.methods
+                .closePlayer()
+                .accounts({
+                    playerData,
+                    run,
+                    playerStats,
+                    nameRegistry,
+                    player: player.publicKey,
+                    clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+                    thread: null,
+                    threadAuthority,
+                })
+                .signers([player])
+                .rpc(),
+            "AlreadyInRun"
+        )
+    })
+
+    it("rejects expire_run before the inactivity deadline", async () => {
+        await expectError(
+            program.methods
+                .expireRun()
+                .accounts({
+                    run,
+                    playerData,
+                    player: player.publicKey,
+                    cleaner: provider.wallet.publicKey,
+                    clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+                    thread: threadAddress("lifecycle_1"),
+                    threadAuthority,
+                    leaderboard,
+                    season,
+                    seasonLeaderboard,
+                    dailyLeaderboard: null,
+                    runRecord: await runRecord(),
+                    playerStats,
+                    crankVault,
+                    programAdmin,
+                    systemProgram: SystemProgram.programId,
+                })
+                .rpc(),
+            "RunNotExpired"
+        )
+    })
+
+    it("rejects card plays while paused and a resume when not paused", async () => {
+        await program.methods.pauseRun().accounts(pauseAccounts("lifecycle_1")).signers([player]).rpc()
+        expect((await program.account.runData.fetch(run)).status).to.deep.equal({ paused: {} })
+
+        await expectError(upgrade(), "RunPaused")
+
+        await program.methods.resumeRun().accounts(pauseAccounts("lifecycle_1")).signers([player]).rpc()
+        await expectError(
+            program.methods.resumeRun().accounts(pauseAccounts("lifecycle_1")).signers([player]).rpc(),
+            "RunNotPaused"
+        )
+        expect((await program.account.runData.fetch(run)).status).to.deep.equal({ active: {} })
+    })
+
+    it("rejects upgrade and a second finish_run after the run is finished", async () => {
+        await finishRun("lifecycle_1")
+
+        await expectError(upgrade(), "NotInRun")
+
+        // the thread is gone with the finished run, so the second finish goes in as a cranked one
+        await expectError(finishRun(null), "NotInRun")
+
+        const data = await program.account.playerData.fetch(playerData)
+        expect(data.runsFinished).to.equal(1)
+        expect(data.isInRun).to.equal(false)
+    })
+
+    it("runs a second slot independently of the first", async () => {
+        const run1 = pda(Buffer.from("run"), player.publicKey.toBuffer(), Buffer.from([1]))
+        await program.methods
+            .openRunSlot(1)
+            .accounts({ run: run1, player: player.publicKey })
+            .signers([player])
+            .rpc()
+
+        await startNewRun(null, null, run1)
+
+        const data = await program.account.playerData.fetch(playerData)
+        expect(data.activeRuns).to.equal(0b10)
+        expect(data.isInRun).to.equal(true)
+        expect((await program.account.runData.fetch(run)).status).to.deep.equal({ idle: {} })
+
+        await expectError(
+            program.methods
+                .closeRunSlot()
+                .accounts({ run: run1, player: player.publicKey })
+                .signers([player])
+                .rpc(),
+            "AlreadyInRun"
+        )
+    })
+
+    it("plays the daily challenge once and ranks it on the day's board", async () => {
+        const day = today()
+        if ((await provider.connection.getAccountInfo(dailyChallenge(day))) === null) {
+            await program.methods
+                .initDailyChallenge(day)
+                .accounts({ dailyChallenge: dailyChallenge(day), dailyLeaderboard: dailyLeaderboard(day) })
+                .rpc()
+        }
+        const challenge = await program.account.dailyChallenge.fetch(dailyChallenge(day))
+
+        await startNewRun(null, dailyChallenge(day))
+        const account = await program.account.runData.fetch(run)
+        expect(account.dailyDay).to.equal(day)
+        expect(account.seed.eq(challenge.seed)).to.equal(true)
+
+        await finishRun(null, dailyLeaderboard(day))
+        const board = await program.account.leaderboard.fetch(dailyLeaderboard(day))
+        expect(board.entries.some((entry) => entry.player.equals(player.publicKey))).to.equal(true)
+
+        await expectError(startNewRun(null, dailyChallenge(day)), "DailyChallengeAlreadyPlayed")
+    })
+
+    it("ranks a hard run on the hard and season boards, not the normal one", async () => {
+        await expectError(
+            startNewRun(null, dailyChallenge(today()), run, { hard: {} }),
+            "DailyChallengeDifficulty"
+        )
+
+        await startNewRun(null, null, run, { hard: {} })
+        expect((await program.account.runData.fetch(run)).difficulty).to.deep.equal({ hard: {} })
+
+        // boards keep one entry per player, their best, so compare against what was there before
+        const playerEntry = async (board: PublicKey) =>
+            (await program.account.leaderboard.fetch(board)).entries.find((entry) =>
+                entry.player.equals(player.publicKey)
+            )
+        const normalBefore = await playerEntry(leaderboard)
+        const seasonBefore = await playerEntry(seasonLeaderboard)
+
+        await finishRun(null, null, hardLeaderboard)
+        const entry = await playerEntry(hardLeaderboard)
+        expect(entry).to.not.equal(undefined)
+
+        expect(await playerEntry(leaderboard)).to.deep.equal(normalBefore)
+        const seasonAfter = await playerEntry(seasonLeaderboard)
+        if (seasonBefore === undefined || entry.score.gt(seasonBefore.score)) {
+            expect(seasonAfter).to.deep.equal(entry)
+        } else {
+            expect(seasonAfter).to.deep.equal(seasonBefore)
+        }
+    })
+
+    it("records the mutators a run was played with", async () => {
+        await expectError(startNewRun(null, null, run, { normal: {} }, 0b1000), "InvalidMutators")
+
+        await startNewRun(null, null, run, { normal: {} }, 0b110)
+        expect((await program.account.runData.fetch(run)).mutators).to.equal(0b110)
+        // a fresh run has no experience, let alone double a card's cost
+        await expectError(upgrade(), "InsufficientExperience")
+
+        const record = await runRecord()
+        await finishRun(null)
+        const recorded = await program.account.runRecord.fetch(record)
+        expect(recorded.mutators).to.equal(0b110)
+        expect(recorded.difficulty).to.deep.equal({ normal: {} })
+        // finished before a wave was cleared, so no extraction bonus
+        expect(recorded.score.toNumber()).to.be.lessThan(100)
+    })
+
+    it("carries on numbering run records after close and re-init", async () => {
+        const run1 = pda(Buffer.from("run"), player.publicKey.toBuffer(), Buffer.from([1]))
+        await finishRun(null, null, leaderboard, run1)
+        const { runsFinished } = await program.account.playerData.fetch(playerData)
+
+        await program.methods
+            .closePlayer()
+            .accounts({
+                playerData,
+                run,
+                playerStats,
+                nameRegistry,
+                player: player.publicKey,
+                clockworkProgram: CLOCKWORK_THREAD_PROGRAM_ID,
+                thread: null,
+                threadAuthority,
+            })
+            .signers([player])
+            .rpc()
+        await initPlayer()
+        expect((await program.account.playerData.fetch(playerData)).runsFinished).to.equal(runsFinished)
+
+        await startNewRun(null)
+        await finishRun(null)
+        expect((await program.account.playerData.fetch(playerData)).runsFinished).to.equal(runsFinished + 1)
+    })
+})